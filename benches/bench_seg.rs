@@ -0,0 +1,227 @@
+extern crate criterion;
+extern crate rand;
+extern crate treez;
+
+use self::rand::Rng;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use std::cmp::{max, min};
+use std::ops::Add;
+
+use treez::segmax::{self, SegMax};
+use treez::segsum::SegSum;
+
+/// summation segment tree with boxed nodes allocated on demand, the layout before the node arena
+mod boxed {
+    use std::cmp::{max, min};
+
+    #[derive(Clone, Copy)]
+    enum Change {
+        Abs(i64),
+        Rel(i64),
+    }
+
+    struct N {
+        l: i64,
+        r: i64,
+        s: i64,
+        left: Option<Box<N>>,
+        right: Option<Box<N>>,
+        mark: Option<Change>,
+    }
+
+    impl N {
+        fn new(l: i64, r: i64) -> Self {
+            Self {
+                l,
+                r,
+                s: 0,
+                left: None,
+                right: None,
+                mark: None,
+            }
+        }
+        fn set_lazy(&mut self, f: Change) {
+            let len = self.r - self.l;
+            self.s = match f {
+                Change::Abs(v) => v * len,
+                Change::Rel(v) => self.s + v * len,
+            };
+            if len > 1 {
+                self.mark = Some(match (self.mark, f) {
+                    (Some(Change::Abs(x)), Change::Rel(y)) => Change::Abs(x + y),
+                    (Some(Change::Rel(x)), Change::Rel(y)) => Change::Rel(x + y),
+                    (_, y) => y,
+                });
+            }
+        }
+        fn extend(&mut self) {
+            let m = (self.l + self.r) / 2;
+            if self.left.is_none() {
+                self.left = Some(Box::new(N::new(self.l, m)));
+            }
+            if self.right.is_none() {
+                self.right = Some(Box::new(N::new(m, self.r)));
+            }
+        }
+        fn push_down(&mut self) {
+            if let Some(f) = self.mark.take() {
+                self.extend();
+                self.left.as_mut().unwrap().set_lazy(f);
+                self.right.as_mut().unwrap().set_lazy(f);
+            }
+        }
+        fn update(&mut self, ll: i64, rr: i64, f: Change) {
+            if ll <= self.l && self.r <= rr {
+                self.set_lazy(f);
+                return;
+            }
+            self.push_down();
+            self.extend();
+            let m = (self.l + self.r) / 2;
+            if ll < m {
+                self.left.as_mut().unwrap().update(ll, min(m, rr), f);
+            }
+            if m < rr {
+                self.right.as_mut().unwrap().update(max(m, ll), rr, f);
+            }
+            self.s = self.left.as_ref().unwrap().s + self.right.as_ref().unwrap().s;
+        }
+        fn query(&mut self, ll: i64, rr: i64) -> i64 {
+            if max(ll, self.l) >= min(rr, self.r) {
+                0
+            } else if ll <= self.l && self.r <= rr {
+                self.s
+            } else {
+                self.push_down();
+                let a = self.left.as_mut().map_or(0, |x| x.query(ll, rr));
+                let b = self.right.as_mut().map_or(0, |x| x.query(ll, rr));
+                a + b
+            }
+        }
+    }
+
+    pub struct SegSumBoxed {
+        r: N,
+    }
+
+    impl SegSumBoxed {
+        pub fn new(l: i64, r: i64) -> Self {
+            Self { r: N::new(l, r) }
+        }
+        pub fn add(&mut self, l: i64, r: i64, delta: i64) {
+            self.r.update(l, r, Change::Rel(delta));
+        }
+        pub fn update(&mut self, l: i64, r: i64, val: i64) {
+            self.r.update(l, r, Change::Abs(val));
+        }
+        pub fn query_range(&mut self, l: i64, r: i64) -> i64 {
+            self.r.query(l, r)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct V(i64);
+
+impl Add for V {
+    type Output = V;
+    fn add(self, other: V) -> V {
+        V(self.0 + other.0)
+    }
+}
+
+impl segmax::Min for V {
+    fn min() -> V {
+        V(i64::MIN)
+    }
+}
+
+const RANGE: i64 = 1 << 30;
+
+/// (op, l, r, val) with op 0: add, 1: assign, 2: query
+fn gen_ops(count: usize) -> Vec<(u8, i64, i64, i64)> {
+    let mut rng = rand::thread_rng();
+    (0..count)
+        .map(|_| {
+            let a = rng.gen_range(0, RANGE);
+            let b = rng.gen_range(0, RANGE);
+            let op = rng.gen_range(0, 3);
+            (op, min(a, b), max(a, b) + 1, rng.gen_range(-100, 100))
+        })
+        .collect()
+}
+
+fn benchmark_seg(c: &mut Criterion) {
+    let count = 100_000;
+    let ops = gen_ops(count);
+
+    let mut group = c.benchmark_group("segment tree mixed operations 100K");
+    group.sample_size(10);
+
+    group.bench_function("SegSum boxed nodes", |bench| {
+        bench.iter(|| {
+            let mut seg = boxed::SegSumBoxed::new(0, RANGE + 1);
+            let mut acc = 0;
+            for &(op, l, r, v) in ops.iter() {
+                match op {
+                    0 => seg.add(l, r, v),
+                    1 => seg.update(l, r, v),
+                    _ => acc += seg.query_range(l, r),
+                }
+            }
+            black_box(acc)
+        })
+    });
+
+    group.bench_function("SegSum arena", |bench| {
+        bench.iter(|| {
+            let mut seg = SegSum::new(0, RANGE + 1);
+            let mut acc = 0;
+            for &(op, l, r, v) in ops.iter() {
+                match op {
+                    0 => seg.add(l, r, &v),
+                    1 => seg.update(l, r, &v),
+                    _ => acc += seg.query_range(l, r),
+                }
+            }
+            black_box(acc)
+        })
+    });
+
+    group.bench_function("SegSum arena with_capacity", |bench| {
+        bench.iter(|| {
+            let mut seg = SegSum::with_capacity(0, RANGE + 1, count * 64);
+            let mut acc = 0;
+            for &(op, l, r, v) in ops.iter() {
+                match op {
+                    0 => seg.add(l, r, &v),
+                    1 => seg.update(l, r, &v),
+                    _ => acc += seg.query_range(l, r),
+                }
+            }
+            black_box(acc)
+        })
+    });
+
+    group.bench_function("SegMax arena", |bench| {
+        bench.iter(|| {
+            let mut seg = SegMax::new(0, RANGE + 1);
+            let mut acc = V(0);
+            for &(op, l, r, v) in ops.iter() {
+                match op {
+                    0 => seg.add(l, r, &V(v)),
+                    1 => seg.assign(l, r, &V(v)),
+                    _ => acc = max(acc, seg.query_range(l, r)),
+                }
+            }
+            black_box(acc)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_seg);
+criterion_main!(benches);
@@ -0,0 +1,103 @@
+extern crate criterion;
+extern crate rand;
+extern crate treez;
+
+use self::rand::Rng;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use std::time::{Duration, Instant};
+
+use treez::treap_par::{self, TreapPar};
+
+fn gen_treap(count: usize, threads: usize) -> TreapPar<i64, i64> {
+    let mut rng = rand::thread_rng();
+    let mut t = TreapPar::new();
+    t.set_par_threads(threads);
+    for _ in 0..count {
+        let k: i64 = rng.gen_range(-1_000_000_000, 1_000_000_000);
+        t.insert(k, k);
+    }
+    t
+}
+
+fn benchmark_treap_par(c: &mut Criterion) {
+    let count = 1_000_000;
+
+    let mut group = c.benchmark_group("treap set operations 1M");
+    group.sample_size(10);
+
+    //at least 2 so the parallel path runs on single core machines too
+    let threads = treap_par::default_par_threads().max(2);
+
+    for t in [1, threads].iter() {
+        let a = gen_treap(count, *t);
+        let b = gen_treap(count, *t);
+        let name = format!("{} threads", t);
+
+        group.bench_function(format!("union {}", name), |bench| {
+            bench.iter_batched(
+                || (a.clone(), b.clone()),
+                |(x, y)| black_box(x.union(y)),
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_function(format!("intersect {}", name), |bench| {
+            bench.iter_batched(
+                || (a.clone(), b.clone()),
+                |(x, y)| black_box(x.intersect(y)),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+
+    report_speedup(count, threads);
+}
+
+type SetOp = fn(TreapPar<i64, i64>, TreapPar<i64, i64>) -> TreapPar<i64, i64>;
+
+/// mean time of runs of f over fresh clones of a and b
+fn time_op(a: &TreapPar<i64, i64>, b: &TreapPar<i64, i64>, runs: u32, f: SetOp) -> Duration {
+    let mut total = Duration::default();
+    for _ in 0..runs {
+        let (x, y) = (a.clone(), b.clone());
+        let start = Instant::now();
+        black_box(f(x, y));
+        total += start.elapsed();
+    }
+    total / runs
+}
+
+/// prints the speedup of the parallel set operations over the sequential ones
+fn report_speedup(count: usize, threads: usize) {
+    let runs = 5;
+    let a = gen_treap(count, 1);
+    let b = gen_treap(count, 1);
+    let (mut pa, mut pb) = (a.clone(), b.clone());
+    pa.set_par_threads(threads);
+    pb.set_par_threads(threads);
+
+    let ops: [(&str, SetOp); 2] = [
+        ("union", |x, y| x.union(y)),
+        ("intersect", |x, y| x.intersect(y)),
+    ];
+
+    for (name, f) in ops.iter() {
+        let seq = time_op(&a, &b, runs, *f);
+        let par = time_op(&pa, &pb, runs, *f);
+        println!(
+            "{} 1M: 1 thread {:?}, {} threads {:?}, speedup {:.2}x",
+            name,
+            seq,
+            threads,
+            par,
+            seq.as_secs_f64() / par.as_secs_f64()
+        );
+    }
+}
+
+criterion_group!(benches, benchmark_treap_par);
+criterion_main!(benches);
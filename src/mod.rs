@@ -1,10 +1,16 @@
 extern crate crossbeam;
 extern crate rand;
 
+pub mod seglazy;
+
 pub mod segsum;
 
+pub mod segsum_persistent;
+
 pub mod segmax;
 
+pub mod segbeats;
+
 pub mod rb;
 
 pub mod prefix;
@@ -28,6 +34,14 @@ pub mod backtrack;
 #[path = "treap2.rs"]
 pub mod treap;
 
+pub mod treap_arena;
+
+pub mod treap_node;
+
+pub mod treap_par;
+
+pub mod treap_persistent;
+
 pub mod queue_monotone;
 
 pub mod lower_bound;
@@ -0,0 +1,488 @@
+//! segment tree beats (Ji driver segment tree) over a dynamic range of i64 indices:
+//! range chmin, chmax, add and assign with sum, max and min queries in amortized O(log^2 n);
+//! nodes are allocated on demand and elements are initially 0,
+//! a node without children holds equal elements so it needs no lazy tag;
+//! sums wrap on overflow and are exact whenever the sum of the queried range fits in i64;
+//! element values are checked, an add that moves an element out of i64 panics
+
+#[cfg(test)]
+use rand::distributions::{Distribution, Uniform};
+#[cfg(test)]
+use rand::thread_rng;
+
+use std::cmp::*;
+#[cfg(test)]
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+///helper function: minimum where None stands for an absent value
+fn min_opt(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (Some(x), Some(y)) => Some(min(x, y)),
+        (x, None) => x,
+        (None, y) => y,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct N {
+    l: i64,
+    r: i64,
+    sum: i64,
+    max1: i64,
+    max2: Option<i64>, //strict second max
+    cnt_max: i64,
+    min1: i64,
+    min2: Option<i64>, //strict second min
+    cnt_min: i64,
+    add: i128, //lazy, pending for children; wider than elements so opposite adds cancel exactly
+    left: Option<Box<N>>,
+    right: Option<Box<N>>,
+}
+
+impl N {
+    pub fn new(l: i64, r: i64, val: i64) -> Self {
+        Self {
+            l,
+            r,
+            sum: val.wrapping_mul(r - l),
+            max1: val,
+            max2: None,
+            cnt_max: r - l,
+            min1: val,
+            min2: None,
+            cnt_min: r - l,
+            add: 0,
+            left: None,
+            right: None,
+        }
+    }
+    fn apply_add(&mut self, v: i128) {
+        let add = |x: i64| i64::try_from(x as i128 + v).expect("element value overflows i64");
+        //truncation keeps v modulo 2^64 for the wrapping sum
+        self.sum = self
+            .sum
+            .wrapping_add((v as i64).wrapping_mul(self.r - self.l));
+        self.max1 = add(self.max1);
+        self.max2 = self.max2.map(add);
+        self.min1 = add(self.min1);
+        self.min2 = self.min2.map(add);
+        if self.left.is_some() {
+            self.add += v;
+        }
+    }
+    ///assumes max2 < x if present
+    fn apply_chmin(&mut self, x: i64) {
+        if self.max1 <= x {
+            return;
+        }
+        let d = x.wrapping_sub(self.max1).wrapping_mul(self.cnt_max);
+        self.sum = self.sum.wrapping_add(d);
+        if self.min1 == self.max1 {
+            self.min1 = x;
+        } else if self.min2 == Some(self.max1) {
+            self.min2 = Some(x);
+        }
+        self.max1 = x;
+    }
+    ///assumes x < min2 if present
+    fn apply_chmax(&mut self, x: i64) {
+        if x <= self.min1 {
+            return;
+        }
+        let d = x.wrapping_sub(self.min1).wrapping_mul(self.cnt_min);
+        self.sum = self.sum.wrapping_add(d);
+        if self.max1 == self.min1 {
+            self.max1 = x;
+        } else if self.max2 == Some(self.min1) {
+            self.max2 = Some(x);
+        }
+        self.min1 = x;
+    }
+    fn push_down(&mut self) {
+        if self.l + 1 >= self.r {
+            return;
+        }
+        if self.left.is_none() {
+            //elements of a node without children are equal
+            let m = (self.l + self.r) / 2;
+            self.left = Some(Box::new(N::new(self.l, m, self.max1)));
+            self.right = Some(Box::new(N::new(m, self.r, self.max1)));
+            return;
+        }
+        let (add, max1, min1) = (self.add, self.max1, self.min1);
+        self.add = 0;
+        for y in self.left.iter_mut().chain(self.right.iter_mut()) {
+            if add != 0 {
+                y.apply_add(add);
+            }
+            if y.max1 > max1 {
+                y.apply_chmin(max1);
+            }
+            if y.min1 < min1 {
+                y.apply_chmax(min1);
+            }
+        }
+    }
+    fn pull(&mut self) {
+        let (a, b) = match (&self.left, &self.right) {
+            (Some(a), Some(b)) => (a, b),
+            _ => panic!("missing children"),
+        };
+        self.sum = a.sum.wrapping_add(b.sum);
+        match a.max1.cmp(&b.max1) {
+            Ordering::Equal => {
+                self.max1 = a.max1;
+                self.cnt_max = a.cnt_max + b.cnt_max;
+                self.max2 = max(a.max2, b.max2);
+            }
+            Ordering::Greater => {
+                self.max1 = a.max1;
+                self.cnt_max = a.cnt_max;
+                self.max2 = max(a.max2, Some(b.max1));
+            }
+            Ordering::Less => {
+                self.max1 = b.max1;
+                self.cnt_max = b.cnt_max;
+                self.max2 = max(Some(a.max1), b.max2);
+            }
+        }
+        match a.min1.cmp(&b.min1) {
+            Ordering::Equal => {
+                self.min1 = a.min1;
+                self.cnt_min = a.cnt_min + b.cnt_min;
+                self.min2 = min_opt(a.min2, b.min2);
+            }
+            Ordering::Less => {
+                self.min1 = a.min1;
+                self.cnt_min = a.cnt_min;
+                self.min2 = min_opt(a.min2, Some(b.min1));
+            }
+            Ordering::Greater => {
+                self.min1 = b.min1;
+                self.cnt_min = b.cnt_min;
+                self.min2 = min_opt(Some(a.min1), b.min2);
+            }
+        }
+    }
+    ///helper function: recurses into both children and recomputes current node
+    fn recurse<F>(&mut self, f: F)
+    where
+        F: Fn(&mut N),
+    {
+        self.push_down();
+        if let Some(ref mut x) = &mut self.left {
+            f(x);
+        }
+        if let Some(ref mut x) = &mut self.right {
+            f(x);
+        }
+        self.pull();
+    }
+    pub fn chmin(&mut self, ll: i64, rr: i64, x: i64) {
+        if max(ll, self.l) >= min(rr, self.r) || self.max1 <= x {
+            return;
+        }
+        if ll <= self.l && self.r <= rr && !matches!(self.max2, Some(y) if y >= x) {
+            self.apply_chmin(x);
+            return;
+        }
+        self.recurse(|c| c.chmin(ll, rr, x));
+    }
+    pub fn chmax(&mut self, ll: i64, rr: i64, x: i64) {
+        if max(ll, self.l) >= min(rr, self.r) || x <= self.min1 {
+            return;
+        }
+        if ll <= self.l && self.r <= rr && !matches!(self.min2, Some(y) if y <= x) {
+            self.apply_chmax(x);
+            return;
+        }
+        self.recurse(|c| c.chmax(ll, rr, x));
+    }
+    pub fn add(&mut self, ll: i64, rr: i64, v: i64) {
+        if max(ll, self.l) >= min(rr, self.r) {
+            return;
+        }
+        if ll <= self.l && self.r <= rr {
+            self.apply_add(v as i128);
+            return;
+        }
+        self.recurse(|c| c.add(ll, rr, v));
+    }
+    pub fn query_sum(&mut self, ll: i64, rr: i64) -> i64 {
+        let (a, b) = (max(ll, self.l), min(rr, self.r));
+        if a >= b {
+            0
+        } else if ll <= self.l && self.r <= rr {
+            self.sum
+        } else if self.left.is_none() {
+            self.max1.wrapping_mul(b - a)
+        } else {
+            self.push_down();
+            let s = self.left.as_mut().map_or(0, |x| x.query_sum(ll, rr));
+            s.wrapping_add(self.right.as_mut().map_or(0, |x| x.query_sum(ll, rr)))
+        }
+    }
+    pub fn query_max(&mut self, ll: i64, rr: i64) -> i64 {
+        if max(ll, self.l) >= min(rr, self.r) {
+            i64::MIN
+        } else if (ll <= self.l && self.r <= rr) || self.left.is_none() {
+            self.max1
+        } else {
+            self.push_down();
+            max(
+                self.left.as_mut().map_or(i64::MIN, |x| x.query_max(ll, rr)),
+                self.right
+                    .as_mut()
+                    .map_or(i64::MIN, |x| x.query_max(ll, rr)),
+            )
+        }
+    }
+    pub fn query_min(&mut self, ll: i64, rr: i64) -> i64 {
+        if max(ll, self.l) >= min(rr, self.r) {
+            i64::MAX
+        } else if (ll <= self.l && self.r <= rr) || self.left.is_none() {
+            self.min1
+        } else {
+            self.push_down();
+            min(
+                self.left.as_mut().map_or(i64::MAX, |x| x.query_min(ll, rr)),
+                self.right
+                    .as_mut()
+                    .map_or(i64::MAX, |x| x.query_min(ll, rr)),
+            )
+        }
+    }
+    #[cfg(test)]
+    pub fn dbg(&self, d_parent: i64, depths: &mut HashMap<i64, i64>) -> usize {
+        *depths.entry(d_parent).or_default() += 1;
+        let mut count = 0;
+        if let Some(ref x) = &self.left {
+            count += x.dbg(d_parent + 1, depths);
+        }
+        if let Some(ref x) = &self.right {
+            count += x.dbg(d_parent + 1, depths);
+        }
+        count + 1
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SegBeats {
+    lim_l: i64,
+    lim_r: i64,
+    r: N,
+}
+
+impl SegBeats {
+    pub fn new(l: i64, r: i64) -> Self {
+        Self {
+            lim_l: l,
+            lim_r: r,
+            r: N::new(l, r, 0),
+        }
+    }
+    fn check(&self, l: i64, r: i64) {
+        assert!(self.lim_l <= l);
+        assert!(r <= self.lim_r);
+    }
+    /// sets elements x in [l,r) to min(x, val)
+    pub fn chmin(&mut self, l: i64, r: i64, val: i64) {
+        if l < r {
+            self.check(l, r);
+            self.r.chmin(l, r, val);
+        }
+    }
+    /// sets elements x in [l,r) to max(x, val)
+    pub fn chmax(&mut self, l: i64, r: i64, val: i64) {
+        if l < r {
+            self.check(l, r);
+            self.r.chmax(l, r, val);
+        }
+    }
+    /// adds delta to elements in [l,r),
+    /// panics if an element leaves the range of i64
+    pub fn add(&mut self, l: i64, r: i64, delta: i64) {
+        if l < r {
+            self.check(l, r);
+            self.r.add(l, r, delta);
+        }
+    }
+    /// sets elements in [l,r) to val
+    pub fn assign(&mut self, l: i64, r: i64, val: i64) {
+        if l < r {
+            self.check(l, r);
+            self.r.chmin(l, r, val);
+            self.r.chmax(l, r, val);
+        }
+    }
+    /// returns sum of elements in [l,r), wrapping on overflow
+    pub fn query_sum(&mut self, l: i64, r: i64) -> i64 {
+        self.check(l, r);
+        self.r.query_sum(l, r)
+    }
+    /// returns max of elements in [l,r), i64::MIN if empty
+    pub fn query_max(&mut self, l: i64, r: i64) -> i64 {
+        self.check(l, r);
+        self.r.query_max(l, r)
+    }
+    /// returns min of elements in [l,r), i64::MAX if empty
+    pub fn query_min(&mut self, l: i64, r: i64) -> i64 {
+        self.check(l, r);
+        self.r.query_min(l, r)
+    }
+    #[cfg(test)]
+    pub fn dbg(&self) {
+        let mut count = 0;
+        let mut depths = HashMap::new();
+        self.r.dbg(0, &mut depths);
+        let mut d_avg = 0.;
+        for (depth, freq) in depths {
+            d_avg += (depth * freq) as f64;
+            count += freq;
+        }
+        d_avg /= count as f64;
+        println!("avg depth per node: {}", d_avg);
+        println!("count nodes: {}", count);
+    }
+}
+
+#[test]
+fn test_seg_beats() {
+    const M: usize = 64;
+    const OFFSET: i64 = -20;
+    let mut seg = SegBeats::new(OFFSET, OFFSET + M as i64);
+    let mut reference = vec![0i64; M];
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M as i64);
+    let distr2 = Uniform::from(0..M as i64 + 1);
+    let distr3 = Uniform::from(-50..51);
+    let distr_op = Uniform::from(0..4);
+
+    for _ in 0..3000 {
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let val: i64 = distr3.sample(&mut g);
+        let (l, r) = (a + OFFSET, b + OFFSET);
+        let range = &mut reference[a as usize..b as usize];
+        match distr_op.sample(&mut g) {
+            0 => {
+                seg.chmin(l, r, val);
+                range.iter_mut().for_each(|x| *x = min(*x, val));
+            }
+            1 => {
+                seg.chmax(l, r, val);
+                range.iter_mut().for_each(|x| *x = max(*x, val));
+            }
+            2 => {
+                seg.add(l, r, val);
+                range.iter_mut().for_each(|x| *x += val);
+            }
+            _ => {
+                seg.assign(l, r, val);
+                range.iter_mut().for_each(|x| *x = val);
+            }
+        }
+
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let range = &reference[a as usize..b as usize];
+        let (l, r) = (a + OFFSET, b + OFFSET);
+        assert_eq!(seg.query_sum(l, r), range.iter().sum::<i64>());
+        assert_eq!(
+            seg.query_max(l, r),
+            range.iter().cloned().max().unwrap_or(i64::MIN)
+        );
+        assert_eq!(
+            seg.query_min(l, r),
+            range.iter().cloned().min().unwrap_or(i64::MAX)
+        );
+    }
+
+    seg.dbg();
+
+    for i in 0..M {
+        for j in i..M + 1 {
+            let range = &reference[i..j];
+            let (l, r) = (i as i64 + OFFSET, j as i64 + OFFSET);
+            assert_eq!(seg.query_sum(l, r), range.iter().sum::<i64>());
+            assert_eq!(
+                seg.query_max(l, r),
+                range.iter().cloned().max().unwrap_or(i64::MIN)
+            );
+            assert_eq!(
+                seg.query_min(l, r),
+                range.iter().cloned().min().unwrap_or(i64::MAX)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_seg_beats_large_range() {
+    //sparse updates over a huge range only allocate nodes along the touched paths
+    let mut seg = SegBeats::new(-1 << 40, 1 << 40);
+    seg.assign(-1 << 40, 1 << 40, 10);
+    seg.chmin(0, 1 << 20, 3);
+    seg.add(5, 7, 100);
+    seg.chmax(-10, 10, 4);
+    assert_eq!(seg.query_sum(0, 10), 4 * 10 + 2 * 100 - 2);
+    assert_eq!(seg.query_max(-1 << 40, 1 << 40), 103);
+    assert_eq!(seg.query_min(-1 << 40, 1 << 40), 3);
+    assert_eq!(seg.query_sum(-5, 0), 50);
+    assert_eq!(seg.query_sum(1 << 20, (1 << 20) + 3), 30);
+}
+
+#[test]
+fn test_seg_beats_extreme_values() {
+    let mut seg = SegBeats::new(0, 8);
+    seg.chmin(0, 8, i64::MIN);
+    assert_eq!(seg.query_max(0, 8), i64::MIN);
+    assert_eq!(seg.query_sum(3, 4), i64::MIN);
+    seg.chmax(0, 8, i64::MAX);
+    assert_eq!(seg.query_min(0, 8), i64::MAX);
+    assert_eq!(seg.query_sum(0, 1), i64::MAX);
+
+    seg.assign(2, 5, i64::MIN);
+    seg.assign(4, 6, i64::MAX);
+    seg.assign(6, 7, 0);
+    //MAX MAX MIN MIN MAX MAX 0 MAX
+    assert_eq!(seg.query_sum(1, 3), -1);
+    assert_eq!(seg.query_sum(3, 5), -1);
+    assert_eq!(seg.query_max(2, 4), i64::MIN);
+    assert_eq!(seg.query_min(4, 8), 0);
+    seg.add(2, 4, 5);
+    assert_eq!(seg.query_min(0, 8), i64::MIN + 5);
+    seg.chmin(0, 8, 7);
+    assert_eq!(seg.query_sum(1, 3), i64::MIN + 12);
+    assert_eq!(seg.query_sum(4, 8), 7 * 3);
+    assert_eq!(seg.query_max(0, 8), 7);
+
+    //extreme tags over a huge range take the tag path instead of visiting every element
+    let mut seg = SegBeats::new(-1 << 40, 1 << 40);
+    seg.add(0, 10, 3);
+    seg.chmin(-1 << 40, 1 << 40, i64::MIN);
+    assert_eq!(seg.query_max(-1 << 40, 1 << 40), i64::MIN);
+    seg.chmax(-1 << 40, 1 << 40, i64::MAX);
+    seg.assign(-1 << 40, 1 << 40, i64::MIN);
+    assert_eq!(seg.query_sum(7, 8), i64::MIN);
+    seg.assign(-1 << 40, 1 << 40, i64::MAX);
+    assert_eq!(seg.query_min(-1 << 40, 1 << 40), i64::MAX);
+
+    //pending adds beyond i64 are exact while the elements fit
+    let mut seg = SegBeats::new(0, 8);
+    seg.assign(0, 8, -i64::MAX);
+    assert_eq!(seg.query_max(0, 1), -i64::MAX);
+    seg.add(0, 8, i64::MAX);
+    seg.add(0, 8, i64::MAX);
+    assert_eq!(seg.query_min(0, 1), i64::MAX);
+    assert_eq!(seg.query_max(7, 8), i64::MAX);
+}
+
+#[test]
+#[should_panic(expected = "element value overflows i64")]
+fn test_seg_beats_add_overflow() {
+    let mut seg = SegBeats::new(0, 8);
+    seg.assign(2, 4, i64::MAX);
+    seg.add(0, 8, 1);
+}
@@ -0,0 +1,737 @@
+//! dynamic lazy segment tree over a range of i64 indices,
+//! generic over a monoid combining values and an action lazily applied to ranges;
+//! nodes are allocated on demand in a contiguous arena and elements are initially the identity of the monoid
+//!
+//! updates push pending marks down, queries take &self and compose the marks on the way down instead,
+//! so a tree behind a shared reference or an Arc can be queried from multiple threads
+
+#[cfg(test)]
+use rand::distributions::{Distribution, Uniform};
+#[cfg(test)]
+use rand::thread_rng;
+
+use std::cmp::*;
+#[cfg(test)]
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+/// associative combine with an identity element, implemented by a marker type
+pub trait Monoid<T> {
+    fn identity() -> T;
+
+    /// combines values of adjacent ranges in index order
+    fn combine(a: &T, b: &T) -> T;
+}
+
+/// lazy tag applied to all elements of a range
+pub trait Action<T>: Clone + Debug {
+    /// returns the aggregate of a range of len elements after applying the tag to all of them
+    fn apply_len(&self, agg: &T, len: i64) -> T;
+
+    /// returns the tag equivalent to applying self followed by other
+    fn compose(&self, other: &Self) -> Self;
+
+    /// returns the element after applying the tag
+    fn apply(&self, val: &T) -> T {
+        self.apply_len(val, 1)
+    }
+
+    /// returns true if the result of the tag does not depend on the previous elements,
+    /// in which case nodes below a range covered by the tag are released for reuse
+    fn overrides(&self) -> bool {
+        false
+    }
+}
+
+/// link to no node, slot 0 holds the root which is never a child
+const NIL: u32 = 0;
+
+#[derive(Debug, Clone)]
+struct N<T, A>
+where
+    T: Debug + Clone,
+    A: Action<T>,
+{
+    s: T, //aggregate including the pending mark
+    left: u32,
+    right: u32,
+    mark: Option<A>, //lazy, pending for children
+}
+
+/// memory usage of the node arena
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemStats {
+    pub live_nodes: usize,
+    pub free_nodes: usize,
+    pub capacity_nodes: usize,
+    pub node_bytes: usize,
+    pub total_bytes: usize, //allocated for nodes and the freelist
+}
+
+#[derive(Debug, Clone)]
+pub struct SegLazy<T, M, A>
+where
+    T: Debug + Clone,
+    M: Monoid<T>,
+    A: Action<T>,
+{
+    lim_l: i64,
+    lim_r: i64,
+    nodes: Vec<N<T, A>>,
+    freelist: Vec<u32>,
+    _m: PhantomData<M>,
+}
+
+impl<T, M, A> SegLazy<T, M, A>
+where
+    T: Debug + Clone,
+    M: Monoid<T>,
+    A: Action<T>,
+{
+    pub fn new(l: i64, r: i64) -> Self {
+        Self::with_capacity(l, r, 1)
+    }
+    /// reserves space for n nodes up front, a range update creates up to about 4 log(r-l) nodes
+    pub fn with_capacity(l: i64, r: i64, n: usize) -> Self {
+        let mut nodes = Vec::with_capacity(max(n, 1));
+        nodes.push(Self::empty_node());
+        Self {
+            lim_l: l,
+            lim_r: r,
+            nodes,
+            freelist: vec![],
+            _m: PhantomData,
+        }
+    }
+    /// builds the tree over [offset, offset + vals.len()) holding vals in O(n),
+    /// space for all of its nodes is reserved up front
+    pub fn from_slice(offset: i64, vals: &[T]) -> Self {
+        let (l, r) = (offset, offset + vals.len() as i64);
+        let mut seg = Self::with_capacity(l, r, 2 * vals.len());
+        if !vals.is_empty() {
+            seg.build(0, l, r, vals);
+        }
+        seg
+    }
+    /// from_slice over the collected items
+    pub fn from_iter<I>(offset: i64, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Self::from_slice(offset, &items.into_iter().collect::<Vec<_>>())
+    }
+    /// returns [l,r) of the indices
+    pub fn bounds(&self) -> (i64, i64) {
+        (self.lim_l, self.lim_r)
+    }
+    pub fn mem_stats(&self) -> MemStats {
+        let node_bytes = size_of::<N<T, A>>();
+        MemStats {
+            live_nodes: self.nodes.len() - self.freelist.len(),
+            free_nodes: self.freelist.len(),
+            capacity_nodes: self.nodes.capacity(),
+            node_bytes,
+            total_bytes: self.nodes.capacity() * node_bytes
+                + self.freelist.capacity() * size_of::<u32>(),
+        }
+    }
+    ///helper function
+    fn empty_node() -> N<T, A> {
+        N {
+            s: M::identity(),
+            left: NIL,
+            right: NIL,
+            mark: None,
+        }
+    }
+    ///helper function
+    fn new_slot(&mut self) -> u32 {
+        match self.freelist.pop() {
+            Some(idx) => {
+                self.nodes[idx as usize] = Self::empty_node();
+                idx
+            }
+            _ => {
+                assert!(self.nodes.len() < u32::MAX as usize);
+                self.nodes.push(Self::empty_node());
+                (self.nodes.len() - 1) as u32
+            }
+        }
+    }
+    ///helper function: recycles all slots below n
+    fn free_children(&mut self, n: u32) {
+        let mut q = vec![n];
+        while let Some(x) = q.pop() {
+            let (a, b) = {
+                let x = &mut self.nodes[x as usize];
+                let links = (x.left, x.right);
+                x.left = NIL;
+                x.right = NIL;
+                links
+            };
+            for c in [a, b].iter().filter(|c| **c != NIL) {
+                q.push(*c);
+                self.nodes[*c as usize].mark = None;
+                self.freelist.push(*c);
+            }
+        }
+    }
+    fn set_lazy(&mut self, n: u32, l: i64, r: i64, f: &A) {
+        let x = &mut self.nodes[n as usize];
+        x.s = f.apply_len(&x.s, r - l);
+        if l + 1 < r {
+            x.mark = Some(match x.mark.take() {
+                Some(g) => g.compose(f),
+                None => f.clone(),
+            });
+            if f.overrides() {
+                self.free_children(n);
+            }
+        }
+    }
+    ///helper function: returns the left child of n, allocating it if needed
+    fn left(&mut self, n: u32) -> u32 {
+        if self.nodes[n as usize].left == NIL {
+            let c = self.new_slot();
+            self.nodes[n as usize].left = c;
+        }
+        self.nodes[n as usize].left
+    }
+    ///helper function: returns the right child of n, allocating it if needed
+    fn right(&mut self, n: u32) -> u32 {
+        if self.nodes[n as usize].right == NIL {
+            let c = self.new_slot();
+            self.nodes[n as usize].right = c;
+        }
+        self.nodes[n as usize].right
+    }
+    fn push_down(&mut self, n: u32, l: i64, r: i64) {
+        if let Some(f) = self.nodes[n as usize].mark.take() {
+            let m = (l + r) / 2;
+            let a = self.left(n);
+            self.set_lazy(a, l, m, &f);
+            let b = self.right(n);
+            self.set_lazy(b, m, r, &f);
+        }
+    }
+    fn pull(&mut self, n: u32) {
+        let (a, b) = (self.nodes[n as usize].left, self.nodes[n as usize].right);
+        let s = match (a, b) {
+            (NIL, NIL) => M::identity(),
+            (a, NIL) => self.nodes[a as usize].s.clone(),
+            (NIL, b) => self.nodes[b as usize].s.clone(),
+            (a, b) => M::combine(&self.nodes[a as usize].s, &self.nodes[b as usize].s),
+        };
+        self.nodes[n as usize].s = s;
+    }
+    fn update(&mut self, n: u32, l: i64, r: i64, ll: i64, rr: i64, f: &A) {
+        if ll <= l && r <= rr {
+            self.set_lazy(n, l, r, f);
+        } else {
+            self.push_down(n, l, r);
+            let m = (l + r) / 2;
+            if ll < m {
+                let a = self.left(n);
+                self.update(a, l, m, ll, min(m, rr), f);
+            }
+            if m < rr {
+                let b = self.right(n);
+                self.update(b, m, r, max(m, ll), rr, f);
+            }
+            self.pull(n);
+            debug_assert!(self.nodes[n as usize].mark.is_none());
+        }
+    }
+    ///helper function: returns the aggregate of node n over [l,r) with the pending tag acc of its ancestors,
+    ///a missing node stands for elements that were never touched
+    fn view(&self, n: Option<u32>, l: i64, r: i64, acc: &Option<A>) -> T {
+        let s = n.map_or_else(M::identity, |x| self.nodes[x as usize].s.clone());
+        match acc {
+            Some(f) => f.apply_len(&s, r - l),
+            None => s,
+        }
+    }
+    ///helper function: returns the children of node n and their pending tag without pushing it down
+    fn descend(&self, n: Option<u32>, acc: &Option<A>) -> (Option<u32>, Option<u32>, Option<A>) {
+        let x = match n {
+            Some(x) => &self.nodes[x as usize],
+            None => return (None, None, acc.clone()),
+        };
+        let link = |c: u32| if c == NIL { None } else { Some(c) };
+        let acc = match (&x.mark, acc) {
+            (Some(g), Some(f)) => Some(g.compose(f)),
+            (g, f) => g.clone().or_else(|| f.clone()),
+        };
+        (link(x.left), link(x.right), acc)
+    }
+    fn query(&self, n: Option<u32>, l: i64, r: i64, ll: i64, rr: i64, acc: &Option<A>) -> T {
+        let (a, b) = (max(ll, l), min(rr, r));
+        if a >= b {
+            M::identity()
+        } else if (ll <= l && r <= rr) || n.is_none() {
+            self.view(n, a, b, acc)
+        } else {
+            let (x, y, acc) = self.descend(n, acc);
+            let m = (l + r) / 2;
+            let s_x = self.query(x, l, m, ll, rr, &acc);
+            let s_y = self.query(y, m, r, ll, rr, &acc);
+            M::combine(&s_x, &s_y)
+        }
+    }
+    fn find_first_in<P>(
+        &self,
+        n: Option<u32>,
+        (l, r): (i64, i64),
+        ll: i64,
+        rr: i64,
+        pred: &P,
+        acc: &Option<A>,
+    ) -> Option<i64>
+    where
+        P: Fn(&T) -> bool,
+    {
+        if ll >= rr || max(ll, l) >= min(rr, r) || !pred(&self.view(n, l, r, acc)) {
+            return None;
+        }
+        if l + 1 >= r {
+            return Some(l);
+        }
+        let m = (l + r) / 2;
+        let (x, y, acc) = self.descend(n, acc);
+        self.find_first_in(x, (l, m), ll, rr, pred, &acc)
+            .or_else(|| self.find_first_in(y, (m, r), ll, rr, pred, &acc))
+    }
+    fn find_last_in<P>(
+        &self,
+        n: Option<u32>,
+        (l, r): (i64, i64),
+        ll: i64,
+        rr: i64,
+        pred: &P,
+        acc: &Option<A>,
+    ) -> Option<i64>
+    where
+        P: Fn(&T) -> bool,
+    {
+        if ll >= rr || max(ll, l) >= min(rr, r) || !pred(&self.view(n, l, r, acc)) {
+            return None;
+        }
+        if l + 1 >= r {
+            return Some(l);
+        }
+        let m = (l + r) / 2;
+        let (x, y, acc) = self.descend(n, acc);
+        self.find_last_in(y, (m, r), ll, rr, pred, &acc)
+            .or_else(|| self.find_last_in(x, (l, m), ll, rr, pred, &acc))
+    }
+    ///returns the index where pred first fails when extending s over [ll,r) in index order
+    fn max_right_in<P>(
+        &self,
+        n: Option<u32>,
+        (l, r): (i64, i64),
+        ll: i64,
+        pred: &P,
+        acc: &Option<A>,
+        s: &mut T,
+    ) -> Option<i64>
+    where
+        P: Fn(&T) -> bool,
+    {
+        if r <= ll {
+            return None;
+        }
+        if ll <= l {
+            let c = M::combine(s, &self.view(n, l, r, acc));
+            if pred(&c) {
+                *s = c;
+                return None;
+            }
+            if l + 1 >= r {
+                return Some(l);
+            }
+        }
+        let m = (l + r) / 2;
+        let (x, y, acc) = self.descend(n, acc);
+        match self.max_right_in(x, (l, m), ll, pred, &acc, s) {
+            Some(i) => Some(i),
+            None => self.max_right_in(y, (m, r), ll, pred, &acc, s),
+        }
+    }
+    ///returns the index after the one where pred first fails when extending s over [l,rr) in reverse
+    fn min_left_in<P>(
+        &self,
+        n: Option<u32>,
+        (l, r): (i64, i64),
+        rr: i64,
+        pred: &P,
+        acc: &Option<A>,
+        s: &mut T,
+    ) -> Option<i64>
+    where
+        P: Fn(&T) -> bool,
+    {
+        if rr <= l {
+            return None;
+        }
+        if r <= rr {
+            let c = M::combine(&self.view(n, l, r, acc), s);
+            if pred(&c) {
+                *s = c;
+                return None;
+            }
+            if l + 1 >= r {
+                return Some(r);
+            }
+        }
+        let m = (l + r) / 2;
+        let (x, y, acc) = self.descend(n, acc);
+        match self.min_left_in(y, (m, r), rr, pred, &acc, s) {
+            Some(i) => Some(i),
+            None => self.min_left_in(x, (l, m), rr, pred, &acc, s),
+        }
+    }
+    ///helper function: fills node n over [l,r) and its subtree with vals, children before parents
+    fn build(&mut self, n: u32, l: i64, r: i64, vals: &[T]) {
+        if l + 1 >= r {
+            self.nodes[n as usize].s = vals[0].clone();
+            return;
+        }
+        let m = (l + r) / 2;
+        let (vals_l, vals_r) = vals.split_at((m - l) as usize);
+        let a = self.left(n);
+        self.build(a, l, m, vals_l);
+        let b = self.right(n);
+        self.build(b, m, r, vals_r);
+        self.pull(n);
+    }
+    ///helper function: appends the elements of node n over [l,r) with the pending tag acc of its ancestors
+    fn collect_into(&self, n: Option<u32>, (l, r): (i64, i64), acc: &Option<A>, out: &mut Vec<T>) {
+        if l >= r {
+            return;
+        }
+        if n.is_none() {
+            let v = self.view(None, l, l + 1, acc);
+            let len = out.len() + (r - l) as usize;
+            out.resize(len, v);
+        } else if l + 1 >= r {
+            out.push(self.view(n, l, r, acc));
+        } else {
+            let m = (l + r) / 2;
+            let (x, y, acc) = self.descend(n, acc);
+            self.collect_into(x, (l, m), &acc, out);
+            self.collect_into(y, (m, r), &acc, out);
+        }
+    }
+    /// applies f to all elements in [l,r)
+    pub fn apply_range(&mut self, l: i64, r: i64, f: &A) {
+        if l < r {
+            assert!(self.lim_l <= l);
+            assert!(r <= self.lim_r);
+            let (lim_l, lim_r) = self.bounds();
+            self.update(0, lim_l, lim_r, l, r, f);
+        }
+    }
+    /// returns combined value of elements in [l,r)
+    pub fn query_range(&self, l: i64, r: i64) -> T {
+        assert!(self.lim_l <= l);
+        assert!(r <= self.lim_r);
+        self.query(Some(0), self.lim_l, self.lim_r, l, r, &None)
+    }
+    /// returns the lowest index in [l,r) whose element satisfies pred in O(log n);
+    /// pred has to hold for the aggregate of a range iff it holds for some element of the range,
+    /// eg: x >= threshold for max
+    pub fn find_first<P>(&self, l: i64, r: i64, pred: P) -> Option<i64>
+    where
+        P: Fn(&T) -> bool,
+    {
+        assert!(self.lim_l <= l);
+        assert!(r <= self.lim_r);
+        self.find_first_in(Some(0), (self.lim_l, self.lim_r), l, r, &pred, &None)
+    }
+    /// returns the highest index in [l,r) whose element satisfies pred in O(log n),
+    /// same requirement on pred as find_first
+    pub fn find_last<P>(&self, l: i64, r: i64, pred: P) -> Option<i64>
+    where
+        P: Fn(&T) -> bool,
+    {
+        assert!(self.lim_l <= l);
+        assert!(r <= self.lim_r);
+        self.find_last_in(Some(0), (self.lim_l, self.lim_r), l, r, &pred, &None)
+    }
+    /// returns the highest r in [l,lim_r] such that pred holds for the combined value of [l,r)
+    /// in O(log n); pred has to hold for the identity and be monotone, ie: once it fails
+    /// for [l,r) it fails for [l,r+1) as well
+    pub fn max_right<P>(&self, l: i64, pred: P) -> i64
+    where
+        P: Fn(&T) -> bool,
+    {
+        assert!(self.lim_l <= l && l <= self.lim_r);
+        debug_assert!(pred(&M::identity()));
+        let mut s = M::identity();
+        self.max_right_in(Some(0), (self.lim_l, self.lim_r), l, &pred, &None, &mut s)
+            .unwrap_or(self.lim_r)
+    }
+    /// returns the lowest l in [lim_l,r] such that pred holds for the combined value of [l,r)
+    /// in O(log n); pred has to hold for the identity and be monotone, ie: once it fails
+    /// for [l,r) it fails for [l-1,r) as well
+    pub fn min_left<P>(&self, r: i64, pred: P) -> i64
+    where
+        P: Fn(&T) -> bool,
+    {
+        assert!(self.lim_l <= r && r <= self.lim_r);
+        debug_assert!(pred(&M::identity()));
+        let mut s = M::identity();
+        self.min_left_in(Some(0), (self.lim_l, self.lim_r), r, &pred, &None, &mut s)
+            .unwrap_or(self.lim_l)
+    }
+    /// returns the elements of [lim_l,lim_r) in index order in O(n)
+    pub fn to_vec(&self) -> Vec<T> {
+        let mut ret = Vec::with_capacity((self.lim_r - self.lim_l) as usize);
+        self.collect_into(Some(0), (self.lim_l, self.lim_r), &None, &mut ret);
+        ret
+    }
+    #[cfg(test)]
+    fn dbg_node(&self, n: u32, l: i64, r: i64, d_parent: i64, depths: &mut HashMap<i64, i64>) {
+        *depths.entry(d_parent).or_default() += 1;
+        let x = &self.nodes[n as usize];
+        let m = (l + r) / 2;
+        if x.left != NIL {
+            self.dbg_node(x.left, l, m, d_parent + 1, depths);
+        }
+        println!("[{},{}), mark: {:?}, s: {:?}", l, r, x.mark, x.s);
+        if x.right != NIL {
+            self.dbg_node(x.right, m, r, d_parent + 1, depths);
+        }
+    }
+    #[cfg(test)]
+    pub fn dbg(&self) {
+        let mut count = 0;
+        let mut depths = HashMap::new();
+        self.dbg_node(0, self.lim_l, self.lim_r, 0, &mut depths);
+        let mut d_avg = 0.;
+        for (depth, freq) in depths {
+            println!("depth: {}, count: {}", depth, freq);
+            d_avg += (depth * freq) as f64;
+            count += freq;
+        }
+        d_avg /= count as f64;
+        println!("avg depth per node: {}", d_avg);
+        println!("count nodes: {}", count);
+        println!("{:?}", self.mem_stats());
+    }
+}
+
+#[cfg(test)]
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+struct Gcd;
+
+#[cfg(test)]
+impl Monoid<i64> for Gcd {
+    fn identity() -> i64 {
+        0
+    }
+    fn combine(a: &i64, b: &i64) -> i64 {
+        gcd(*a, *b)
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+struct Assign(i64);
+
+#[cfg(test)]
+impl Action<i64> for Assign {
+    fn apply_len(&self, _agg: &i64, _len: i64) -> i64 {
+        self.0.abs()
+    }
+    fn compose(&self, other: &Self) -> Self {
+        other.clone()
+    }
+    fn overrides(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+struct Xor;
+
+#[cfg(test)]
+impl Monoid<u64> for Xor {
+    fn identity() -> u64 {
+        0
+    }
+    fn combine(a: &u64, b: &u64) -> u64 {
+        a ^ b
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+struct XorWith(u64);
+
+#[cfg(test)]
+impl Action<u64> for XorWith {
+    fn apply_len(&self, agg: &u64, len: i64) -> u64 {
+        if len % 2 == 1 {
+            agg ^ self.0
+        } else {
+            *agg
+        }
+    }
+    fn compose(&self, other: &Self) -> Self {
+        XorWith(self.0 ^ other.0)
+    }
+}
+
+/// 2x2 matrix product, not commutative
+#[cfg(test)]
+struct MatMul;
+
+#[cfg(test)]
+type Mat = [[i64; 2]; 2];
+
+#[cfg(test)]
+fn mat_mul(a: &Mat, b: &Mat) -> Mat {
+    let mut c = [[0; 2]; 2];
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                c[i][j] = (c[i][j] + a[i][k] * b[k][j]) % 1_000_003;
+            }
+        }
+    }
+    c
+}
+
+#[cfg(test)]
+fn mat_pow(a: &Mat, n: i64) -> Mat {
+    let mut ret = [[1, 0], [0, 1]];
+    for _ in 0..n {
+        ret = mat_mul(&ret, a);
+    }
+    ret
+}
+
+#[cfg(test)]
+impl Monoid<Mat> for MatMul {
+    fn identity() -> Mat {
+        [[1, 0], [0, 1]]
+    }
+    fn combine(a: &Mat, b: &Mat) -> Mat {
+        mat_mul(a, b)
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+struct AssignMat(Mat);
+
+#[cfg(test)]
+impl Action<Mat> for AssignMat {
+    fn apply_len(&self, _agg: &Mat, len: i64) -> Mat {
+        mat_pow(&self.0, len)
+    }
+    fn compose(&self, other: &Self) -> Self {
+        other.clone()
+    }
+    fn overrides(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn test_seg_lazy() {
+    const M: usize = 64;
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M as i64);
+    let distr2 = Uniform::from(0..M as i64 + 1);
+    let distr3 = Uniform::from(1..100);
+
+    let mut seg_gcd = SegLazy::<i64, Gcd, Assign>::new(0, M as i64);
+    let mut seg_xor = SegLazy::<u64, Xor, XorWith>::new(0, M as i64);
+    let mut seg_mat = SegLazy::<Mat, MatMul, AssignMat>::new(0, M as i64);
+    let mut reference_gcd = vec![0i64; M];
+    let mut reference_xor = vec![0u64; M];
+    let mut reference_mat = vec![MatMul::identity(); M];
+
+    for _ in 0..1000 {
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let v: i64 = distr3.sample(&mut g);
+        seg_gcd.apply_range(a, b, &Assign(v * 6));
+        seg_xor.apply_range(a, b, &XorWith(v as u64));
+        let mat = [[v % 3, 1], [1, v % 5]];
+        seg_mat.apply_range(a, b, &AssignMat(mat));
+        for j in a..b {
+            reference_gcd[j as usize] = v * 6;
+            reference_xor[j as usize] ^= v as u64;
+            reference_mat[j as usize] = mat;
+        }
+    }
+
+    seg_gcd.dbg();
+
+    for i in 0..M {
+        for j in i..M + 1 {
+            let expect = reference_gcd[i..j].iter().fold(0, |acc, x| gcd(acc, *x));
+            assert_eq!(seg_gcd.query_range(i as i64, j as i64), expect);
+            let expect = reference_xor[i..j].iter().fold(0, |acc, x| acc ^ x);
+            assert_eq!(seg_xor.query_range(i as i64, j as i64), expect);
+            let expect = reference_mat[i..j]
+                .iter()
+                .fold(MatMul::identity(), |acc, x| mat_mul(&acc, x));
+            assert_eq!(seg_mat.query_range(i as i64, j as i64), expect);
+        }
+    }
+}
+
+#[test]
+fn test_seg_lazy_arena() {
+    const M: i64 = 1 << 20;
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M);
+
+    let mut seg = SegLazy::<u64, Xor, XorWith>::with_capacity(0, M, 1000);
+    let stats = seg.mem_stats();
+    assert_eq!(stats.live_nodes, 1);
+    assert!(stats.capacity_nodes >= 1000);
+    for _ in 0..200 {
+        let a = distr.sample(&mut g);
+        seg.apply_range(a, a + 1, &XorWith(1));
+    }
+    let stats = seg.mem_stats();
+    assert_eq!(stats.free_nodes, 0);
+    assert!(stats.live_nodes > 200);
+    assert!(stats.total_bytes >= stats.capacity_nodes * stats.node_bytes);
+
+    //assigning a covering range releases nodes below it and later updates reuse them
+    let positions = (0..200).map(|_| distr.sample(&mut g)).collect::<Vec<_>>();
+    let mut seg = SegLazy::<i64, Gcd, Assign>::new(0, M);
+    let mut slots = vec![];
+    for v in [4, 10, 14].iter() {
+        for a in positions.iter() {
+            seg.apply_range(*a, *a + 1, &Assign(6));
+        }
+        let stats = seg.mem_stats();
+        slots.push(stats.live_nodes + stats.free_nodes);
+        seg.apply_range(0, M, &Assign(*v));
+        let stats = seg.mem_stats();
+        assert_eq!(stats.live_nodes, 1);
+        assert_eq!(stats.live_nodes + stats.free_nodes, *slots.last().unwrap());
+    }
+    assert_eq!(slots[1], slots[2]);
+    assert_eq!(seg.query_range(0, M), 14);
+    seg.apply_range(0, M / 2, &Assign(12));
+    assert_eq!(seg.query_range(0, M / 2), 12);
+    assert_eq!(seg.query_range(M / 2, M), 14);
+}
@@ -4,10 +4,15 @@ use rand::distributions::{Distribution, Uniform};
 use rand::thread_rng;
 
 use std::cmp::*;
-#[cfg(test)]
-use std::collections::HashMap;
 use std::fmt::Debug;
+use std::marker::PhantomData;
 use std::ops::Add;
+#[cfg(test)]
+use std::sync::Arc;
+#[cfg(test)]
+use std::thread;
+
+use seglazy::{Action, MemStats, Monoid, SegLazy};
 
 pub trait Min {
     fn min() -> Self;
@@ -17,299 +22,337 @@ pub trait Max {
     fn max() -> Self;
 }
 
+/// max of elements, Min::min is the identity
+#[derive(Debug, Clone, Copy)]
+pub struct OpMax;
+
+impl<T> Monoid<T> for OpMax
+where
+    T: Ord + Clone + Min,
+{
+    fn identity() -> T {
+        Min::min()
+    }
+    fn combine(a: &T, b: &T) -> T {
+        max(a, b).clone()
+    }
+}
+
+/// min of elements, Max::max is the identity
+#[derive(Debug, Clone, Copy)]
+pub struct OpMin;
+
+impl<T> Monoid<T> for OpMin
+where
+    T: Ord + Clone + Max,
+{
+    fn identity() -> T {
+        Max::max()
+    }
+    fn combine(a: &T, b: &T) -> T {
+        min(a, b).clone()
+    }
+}
+
+/// element x becomes min(max(x + rel, lo), hi) where lo <= hi,
+/// missing bounds are unbounded; M selects the identity that stands for an empty range
 #[derive(Debug, Clone, Copy)]
-enum Change<T>
+struct Change<T, M> {
+    rel: Option<T>,
+    lo: Option<T>,
+    hi: Option<T>,
+    _m: PhantomData<M>,
+}
+
+///helper function
+fn add_opt<T>(a: Option<T>, b: &Option<T>) -> Option<T>
+where
+    T: Add<Output = T> + Clone,
+{
+    match (a, b) {
+        (Some(x), Some(y)) => Some(x + y.clone()),
+        (None, y) => y.clone(),
+        (x, None) => x,
+    }
+}
+
+///helper function: clamps x to [lo, hi] where missing bounds are unbounded
+fn clamp_opt<T>(x: T, lo: &Option<T>, hi: &Option<T>) -> T
+where
+    T: Ord + Clone,
+{
+    let x = match lo {
+        Some(a) => max(x, a.clone()),
+        None => x,
+    };
+    match hi {
+        Some(b) => min(x, b.clone()),
+        None => x,
+    }
+}
+
+impl<T, M> Change<T, M>
+where
+    T: Add<Output = T> + Ord + Debug + Clone,
+{
+    fn new(rel: Option<T>, lo: Option<T>, hi: Option<T>) -> Self {
+        Self {
+            rel,
+            lo,
+            hi,
+            _m: PhantomData,
+        }
+    }
+    ///helper function: inf is kept by rel since it stands for an empty range
+    fn apply_inf(&self, agg: &T, inf: &T) -> T {
+        let x = match self.rel {
+            Some(ref d) if agg != inf => agg.clone() + d.clone(),
+            _ => agg.clone(),
+        };
+        clamp_opt(x, &self.lo, &self.hi)
+    }
+    ///helper function: clamping to a single value discards the previous element
+    fn is_assign(&self) -> bool {
+        match (&self.lo, &self.hi) {
+            (Some(a), Some(b)) => a >= b,
+            _ => false,
+        }
+    }
+    fn compose_clamp(&self, other: &Self) -> Self {
+        //shift bounds of self by rel of other, then clamp them by bounds of other
+        let shift = |b: &Option<T>| {
+            b.clone().map(|x| match other.rel {
+                Some(ref d) => x + d.clone(),
+                None => x,
+            })
+        };
+        let lo = match shift(&self.lo) {
+            Some(x) => Some(clamp_opt(x, &other.lo, &other.hi)),
+            None => other.lo.clone(),
+        };
+        let hi = match shift(&self.hi) {
+            Some(x) => Some(clamp_opt(x, &other.lo, &other.hi)),
+            None => other.hi.clone(),
+        };
+        Self::new(add_opt(self.rel.clone(), &other.rel), lo, hi)
+    }
+}
+
+impl<T> Action<T> for Change<T, OpMax>
 where
     T: Add<Output = T> + Ord + Debug + Clone + Min,
 {
-    Abs(T),
-    Rel(T),
+    fn apply_len(&self, agg: &T, _len: i64) -> T {
+        self.apply_inf(agg, &Min::min())
+    }
+    fn compose(&self, other: &Self) -> Self {
+        self.compose_clamp(other)
+    }
+    fn overrides(&self) -> bool {
+        self.is_assign()
+    }
+}
+
+impl<T> Action<T> for Change<T, OpMin>
+where
+    T: Add<Output = T> + Ord + Debug + Clone + Max,
+{
+    fn apply_len(&self, agg: &T, _len: i64) -> T {
+        self.apply_inf(agg, &Max::max())
+    }
+    fn compose(&self, other: &Self) -> Self {
+        self.compose_clamp(other)
+    }
+    fn overrides(&self) -> bool {
+        self.is_assign()
+    }
 }
 
 #[derive(Debug, Clone)]
-struct N<T>
+pub struct SegMax<T>
 where
     T: Add<Output = T> + Ord + Debug + Clone + Min,
 {
-    l: i64,
-    r: i64,
-    s: T,
-    left: Option<Box<N<T>>>,
-    right: Option<Box<N<T>>>,
-    mark: Option<Change<T>>, //lazy
+    seg: SegLazy<T, OpMax, Change<T, OpMax>>,
 }
 
-impl<T> N<T>
+impl<T> SegMax<T>
 where
     T: Add<Output = T> + Ord + Debug + Clone + Min,
 {
     pub fn new(l: i64, r: i64) -> Self {
         Self {
-            l: l,
-            r: r,
-            s: Min::min(),
-            left: None,
-            right: None,
-            mark: None,
+            seg: SegLazy::new(l, r),
         }
     }
-    fn set_lazy_abs(&mut self, val: T) {
-        self.push_down();
-        self.s = max(self.range_val(), val.clone());
-        self.mark = Some(Change::Abs(val));
-    }
-    fn set_lazy_rel(&mut self, val: T) {
-        match &self.mark {
-            Some(Change::Abs(_)) => {
-                self.push_down_abs();
-                assert!(self.mark.is_none());
-            }
-            _ => {}
-        }
-        match &mut self.mark {
-            Some(Change::Rel(ref mut v)) => {
-                *v = v.clone() + val;
-            }
-            _ => {
-                self.mark = Some(Change::Rel(val));
-            }
+    /// reserves space for n nodes
+    pub fn with_capacity(l: i64, r: i64, n: usize) -> Self {
+        Self {
+            seg: SegLazy::with_capacity(l, r, n),
         }
     }
-    pub fn range_val(&self) -> T {
-        match &self.mark {
-            Some(Change::Abs(_)) => self.s.clone(),
-            Some(Change::Rel(v)) => self.s.clone() + v.clone(),
-            _ => self.s.clone(),
+    /// see SegLazy::from_slice
+    pub fn from_slice(offset: i64, vals: &[T]) -> Self {
+        Self {
+            seg: SegLazy::from_slice(offset, vals),
         }
     }
-    fn extend(&mut self) {
-        if self.l + 1 >= self.r {
-            return;
-        }
-        self.extend_left();
-        self.extend_right();
-    }
-    fn extend_left(&mut self) {
-        let m = (self.l + self.r) / 2;
-        if self.l < self.r && self.l < m {
-            match self.left {
-                None => {
-                    self.left = Some(Box::new(N::new(self.l, m)));
-                }
-                _ => {}
-            }
+    /// see SegLazy::from_iter
+    pub fn from_iter<I>(offset: i64, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Self {
+            seg: SegLazy::from_iter(offset, items),
         }
     }
-    fn extend_right(&mut self) {
-        let m = (self.l + self.r) / 2;
-        if self.l < self.r && m < self.r {
-            match self.right {
-                None => {
-                    self.right = Some(Box::new(N::new(m, self.r)));
-                }
-                _ => {}
-            }
-        }
+    /// returns the elements of the whole range in index order in O(n)
+    pub fn to_vec(&self) -> Vec<T> {
+        self.seg.to_vec()
     }
-    fn push_down_abs(&mut self) {
-        match &mut self.mark {
-            Some(Change::Abs(_)) => match self.mark.take() {
-                Some(Change::Abs(x)) => {
-                    self.extend();
-                    if let Some(ref mut y) = &mut self.left {
-                        y.set_lazy_abs(x.clone());
-                    }
-                    if let Some(ref mut y) = &mut self.right {
-                        y.set_lazy_abs(x.clone());
-                    }
-                }
-                _ => {
-                    panic!();
-                }
-            },
-            _ => {}
-        }
+    pub fn mem_stats(&self) -> MemStats {
+        self.seg.mem_stats()
     }
-    fn push_down_rel(&mut self) {
-        match &mut self.mark {
-            Some(Change::Rel(_)) => match self.mark.take() {
-                Some(Change::Rel(x)) => {
-                    self.extend();
-                    if let Some(ref mut y) = &mut self.left {
-                        y.set_lazy_rel(x.clone());
-                    }
-                    if let Some(ref mut y) = &mut self.right {
-                        y.set_lazy_rel(x.clone());
-                    }
-                    self.s = self.s.clone() + x;
-                }
-                _ => {
-                    panic!();
-                }
-            },
-            _ => {}
-        }
+    /// adds delta to elements in [l,r)
+    pub fn add(&mut self, l: i64, r: i64, delta: &T) {
+        let f = Change::new(Some(delta.clone()), None, None);
+        self.seg.apply_range(l, r, &f);
     }
-    fn push_down(&mut self) {
-        if let Some(ref x) = &self.mark {
-            match x {
-                Change::Abs(_) => {
-                    self.push_down_abs();
-                }
-                _ => {
-                    self.push_down_rel();
-                }
-            }
-        }
+    /// sets elements in [l,r) to val
+    pub fn assign(&mut self, l: i64, r: i64, val: &T) {
+        let f = Change::new(None, Some(val.clone()), Some(val.clone()));
+        self.seg.apply_range(l, r, &f);
     }
-    pub fn update(&mut self, ll: i64, rr: i64, val: &T) {
-        if ll <= self.l && self.r <= rr {
-            self.set_lazy_abs(val.clone());
-        } else {
-            self.push_down();
-            let m = (self.l + self.r) / 2;
-            if ll < m {
-                self.extend_left();
-                match &mut self.left {
-                    Some(ref mut x) => {
-                        x.update(ll, min(m, rr), val);
-                    }
-                    _ => {}
-                }
-            }
-            if m < rr {
-                self.extend_right();
-                match &mut self.right {
-                    Some(ref mut x) => {
-                        x.update(max(m, ll), rr, val);
-                    }
-                    _ => {}
-                }
-            }
-            self.s = max(
-                self.left.as_mut().map_or(Min::min(), |x| x.range_val()),
-                self.right.as_mut().map_or(Min::min(), |x| x.range_val()),
-            );
-            debug_assert!(self.mark.is_none());
-        }
+    /// sets elements x in [l,r) to max(x, val)
+    pub fn chmax(&mut self, l: i64, r: i64, val: &T) {
+        let f = Change::new(None, Some(val.clone()), None);
+        self.seg.apply_range(l, r, &f);
     }
-    pub fn add(&mut self, ll: i64, rr: i64, delta: &T) {
-        if ll <= self.l && self.r <= rr {
-            self.set_lazy_rel(delta.clone());
-        } else {
-            self.push_down();
-            let m = (self.l + self.r) / 2;
-            if ll < m {
-                self.extend();
-                if let Some(ref mut x) = &mut self.left {
-                    x.add(ll, min(m, rr), delta);
-                }
-            }
-            if m < rr {
-                self.extend();
-                if let Some(ref mut x) = &mut self.right {
-                    x.add(max(m, ll), rr, delta);
-                }
-            }
-            self.s = max(
-                self.left.as_mut().map_or(Min::min(), |x| x.range_val()),
-                self.right.as_mut().map_or(Min::min(), |x| x.range_val()),
-            );
-            debug_assert!(self.mark.is_none());
-        }
+    /// sets elements x in [l,r) to min(x, val)
+    pub fn chmin(&mut self, l: i64, r: i64, val: &T) {
+        let f = Change::new(None, None, Some(val.clone()));
+        self.seg.apply_range(l, r, &f);
     }
-    pub fn query(&mut self, ll: i64, rr: i64) -> T {
-        if ll >= rr || max(ll, self.l) >= min(rr, self.r) {
-            Min::min()
-        } else if ll <= self.l && self.r <= rr {
-            self.range_val()
-        } else {
-            self.push_down();
-            // let mut ret: T = Default::default();
-            let m = (self.l + self.r) / 2;
-            let a = match &mut self.left {
-                Some(ref mut x) if ll < m => x.query(ll, min(m, rr)),
-                _ => Min::min(),
-            };
-            let b = match &mut self.right {
-                Some(ref mut x) if m < rr => x.query(max(m, ll), rr),
-                _ => Min::min(),
-            };
-            max(a, b)
-        }
+    pub fn query_range(&self, l: i64, r: i64) -> T {
+        self.seg.query_range(l, r)
+    }
+    /// returns (index, max) in [l,r) where index is the leftmost position of the max
+    pub fn query_range_arg(&self, l: i64, r: i64) -> (i64, T) {
+        assert!(l < r);
+        let v = self.seg.query_range(l, r);
+        let i = self
+            .seg
+            .find_first(l, r, |x| *x >= v)
+            .expect("max not found");
+        (i, v)
+    }
+    /// returns the leftmost index in [l,r) with element >= threshold in O(log n)
+    pub fn find_first_ge(&self, l: i64, r: i64, threshold: &T) -> Option<i64> {
+        self.seg.find_first(l, r, |x| *x >= *threshold)
+    }
+    /// returns the rightmost index in [l,r) with element >= threshold in O(log n)
+    pub fn find_last_ge(&self, l: i64, r: i64, threshold: &T) -> Option<i64> {
+        self.seg.find_last(l, r, |x| *x >= *threshold)
+    }
+    /// same as chmax
+    pub fn update(&mut self, l: i64, r: i64, val: &T) {
+        self.chmax(l, r, val);
     }
     #[cfg(test)]
-    pub fn dbg(&self, d_parent: i64, depths: &mut HashMap<i64, i64>) -> usize {
-        *depths.entry(d_parent).or_default() += 1;
-        let mut count = 0;
-        if let Some(ref x) = &self.left {
-            count += x.dbg(d_parent + 1, depths);
-        }
-        println!(
-            "[{},{}), mark: {:?}, s: {:?}",
-            self.l, self.r, self.mark, self.s
-        );
-        if let Some(ref x) = &self.right {
-            count += x.dbg(d_parent + 1, depths);
-        }
-        count + 1
+    pub fn dbg(&self) {
+        self.seg.dbg();
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct SegMax<T>
+pub struct SegMin<T>
 where
-    T: Add<Output = T> + Ord + Debug + Clone + Min,
+    T: Add<Output = T> + Ord + Debug + Clone + Max,
 {
-    lim_l: i64,
-    lim_r: i64,
-    r: N<T>,
+    seg: SegLazy<T, OpMin, Change<T, OpMin>>,
 }
 
-impl<T> SegMax<T>
+impl<T> SegMin<T>
 where
-    T: Add<Output = T> + Ord + Debug + Clone + Min,
+    T: Add<Output = T> + Ord + Debug + Clone + Max,
 {
     pub fn new(l: i64, r: i64) -> Self {
         Self {
-            lim_l: l,
-            lim_r: r,
-            r: N::new(l, r),
+            seg: SegLazy::new(l, r),
         }
     }
-    pub fn add(&mut self, l: i64, r: i64, delta: &T) {
-        if l < r {
-            assert!(self.lim_l <= l);
-            assert!(r <= self.lim_r);
-            self.r.add(l, r, delta);
+    /// reserves space for n nodes
+    pub fn with_capacity(l: i64, r: i64, n: usize) -> Self {
+        Self {
+            seg: SegLazy::with_capacity(l, r, n),
         }
     }
-    pub fn query_range(&mut self, l: i64, r: i64) -> T {
-        assert!(self.lim_l <= l);
-        assert!(r <= self.lim_r);
-        self.r.query(l, r)
+    /// see SegLazy::from_slice
+    pub fn from_slice(offset: i64, vals: &[T]) -> Self {
+        Self {
+            seg: SegLazy::from_slice(offset, vals),
+        }
     }
-    pub fn update(&mut self, l: i64, r: i64, val: &T) {
-        if l < r {
-            assert!(self.lim_l <= l);
-            assert!(r <= self.lim_r);
-            self.r.update(l, r, val);
+    /// see SegLazy::from_iter
+    pub fn from_iter<I>(offset: i64, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Self {
+            seg: SegLazy::from_iter(offset, items),
         }
     }
+    /// returns the elements of the whole range in index order in O(n)
+    pub fn to_vec(&self) -> Vec<T> {
+        self.seg.to_vec()
+    }
+    pub fn mem_stats(&self) -> MemStats {
+        self.seg.mem_stats()
+    }
+    /// adds delta to elements in [l,r)
+    pub fn add(&mut self, l: i64, r: i64, delta: &T) {
+        let f = Change::new(Some(delta.clone()), None, None);
+        self.seg.apply_range(l, r, &f);
+    }
+    /// sets elements in [l,r) to val
+    pub fn assign(&mut self, l: i64, r: i64, val: &T) {
+        let f = Change::new(None, Some(val.clone()), Some(val.clone()));
+        self.seg.apply_range(l, r, &f);
+    }
+    /// sets elements x in [l,r) to max(x, val)
+    pub fn chmax(&mut self, l: i64, r: i64, val: &T) {
+        let f = Change::new(None, Some(val.clone()), None);
+        self.seg.apply_range(l, r, &f);
+    }
+    /// sets elements x in [l,r) to min(x, val)
+    pub fn chmin(&mut self, l: i64, r: i64, val: &T) {
+        let f = Change::new(None, None, Some(val.clone()));
+        self.seg.apply_range(l, r, &f);
+    }
+    pub fn query_range(&self, l: i64, r: i64) -> T {
+        self.seg.query_range(l, r)
+    }
+    /// returns (index, min) in [l,r) where index is the leftmost position of the min
+    pub fn query_range_arg(&self, l: i64, r: i64) -> (i64, T) {
+        assert!(l < r);
+        let v = self.seg.query_range(l, r);
+        let i = self
+            .seg
+            .find_first(l, r, |x| *x <= v)
+            .expect("min not found");
+        (i, v)
+    }
+    /// returns the leftmost index in [l,r) with element <= threshold in O(log n)
+    pub fn find_first_le(&self, l: i64, r: i64, threshold: &T) -> Option<i64> {
+        self.seg.find_first(l, r, |x| *x <= *threshold)
+    }
+    /// returns the rightmost index in [l,r) with element <= threshold in O(log n)
+    pub fn find_last_le(&self, l: i64, r: i64, threshold: &T) -> Option<i64> {
+        self.seg.find_last(l, r, |x| *x <= *threshold)
+    }
     #[cfg(test)]
     pub fn dbg(&self) {
-        let mut count = 0;
-        let mut depths = HashMap::new();
-        self.r.dbg(0, &mut depths);
-        let mut d_avg = 0.;
-        for (depth, freq) in depths {
-            println!("depth: {}, count: {}", depth, freq);
-            d_avg += (depth * freq) as f64;
-            count += freq;
-        }
-        d_avg /= count as f64;
-        println!("avg depth per node: {}", d_avg);
-        println!("count nodes: {}", count);
+        self.seg.dbg();
     }
 }
 
@@ -319,6 +362,13 @@ impl Min for i64 {
         i64::MIN
     }
 }
+
+#[cfg(test)]
+impl Max for i64 {
+    fn max() -> i64 {
+        i64::MAX
+    }
+}
 #[test]
 fn test_seg() {
     const M: usize = 64;
@@ -378,3 +428,260 @@ fn test_seg() {
         }
     }
 }
+
+#[test]
+fn test_seg_assign_chmin_chmax() {
+    const M: usize = 64;
+    let mut seg_max = SegMax::new(0, M as i64);
+    let mut seg_min = SegMin::new(0, M as i64);
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M as i64);
+    let distr2 = Uniform::from(0..M as i64 + 1);
+    let distr3 = Uniform::from(-10..11);
+    let distr_op = Uniform::from(0..4);
+
+    let mut reference = vec![0; M];
+    seg_max.assign(0, M as i64, &0);
+    seg_min.assign(0, M as i64, &0);
+
+    for _ in 0..2000 {
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let val: i64 = distr3.sample(&mut g);
+        match distr_op.sample(&mut g) {
+            0 => {
+                seg_max.add(a, b, &val);
+                seg_min.add(a, b, &val);
+                for h in a..b {
+                    reference[h as usize] += val;
+                }
+            }
+            1 => {
+                seg_max.assign(a, b, &val);
+                seg_min.assign(a, b, &val);
+                for h in a..b {
+                    reference[h as usize] = val;
+                }
+            }
+            2 => {
+                seg_max.chmax(a, b, &val);
+                seg_min.chmax(a, b, &val);
+                for h in a..b {
+                    reference[h as usize] = max(reference[h as usize], val);
+                }
+            }
+            _ => {
+                seg_max.chmin(a, b, &val);
+                seg_min.chmin(a, b, &val);
+                for h in a..b {
+                    reference[h as usize] = min(reference[h as usize], val);
+                }
+            }
+        }
+    }
+
+    for i in 0..M {
+        for j in i + 1..M + 1 {
+            let expect_max = reference[i..j].iter().max().cloned().unwrap();
+            let expect_min = reference[i..j].iter().min().cloned().unwrap();
+            assert_eq!(seg_max.query_range(i as i64, j as i64), expect_max);
+            assert_eq!(seg_min.query_range(i as i64, j as i64), expect_min);
+        }
+    }
+    assert_eq!(seg_max.query_range(5, 5), i64::MIN);
+    assert_eq!(seg_min.query_range(5, 5), i64::MAX);
+}
+
+#[test]
+fn test_seg_arg_find() {
+    const M: usize = 64;
+    let mut seg_max = SegMax::new(0, M as i64);
+    let mut seg_min = SegMin::new(0, M as i64);
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M as i64);
+    let distr2 = Uniform::from(0..M as i64 + 1);
+    let distr3 = Uniform::from(-10..11);
+
+    let mut reference = vec![0; M];
+    seg_max.assign(0, M as i64, &0);
+    seg_min.assign(0, M as i64, &0);
+
+    for _ in 0..300 {
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let val: i64 = distr3.sample(&mut g);
+        if distr.sample(&mut g) % 2 == 0 {
+            seg_max.add(a, b, &val);
+            seg_min.add(a, b, &val);
+            for h in a..b {
+                reference[h as usize] += val;
+            }
+        } else {
+            seg_max.assign(a, b, &val);
+            seg_min.assign(a, b, &val);
+            for h in a..b {
+                reference[h as usize] = val;
+            }
+        }
+    }
+
+    for i in 0..M {
+        for j in i + 1..M + 1 {
+            let expect_max = reference[i..j].iter().max().cloned().unwrap();
+            let pos_max = (i..j).find(|x| reference[*x] == expect_max).unwrap();
+            assert_eq!(
+                seg_max.query_range_arg(i as i64, j as i64),
+                (pos_max as i64, expect_max)
+            );
+            let expect_min = reference[i..j].iter().min().cloned().unwrap();
+            let pos_min = (i..j).find(|x| reference[*x] == expect_min).unwrap();
+            assert_eq!(
+                seg_min.query_range_arg(i as i64, j as i64),
+                (pos_min as i64, expect_min)
+            );
+
+            let threshold: i64 = distr3.sample(&mut g);
+            assert_eq!(
+                seg_max.find_first_ge(i as i64, j as i64, &threshold),
+                (i..j)
+                    .find(|x| reference[*x] >= threshold)
+                    .map(|x| x as i64)
+            );
+            assert_eq!(
+                seg_max.find_last_ge(i as i64, j as i64, &threshold),
+                (i..j)
+                    .rev()
+                    .find(|x| reference[*x] >= threshold)
+                    .map(|x| x as i64)
+            );
+            assert_eq!(
+                seg_min.find_first_le(i as i64, j as i64, &threshold),
+                (i..j)
+                    .find(|x| reference[*x] <= threshold)
+                    .map(|x| x as i64)
+            );
+            assert_eq!(
+                seg_min.find_last_le(i as i64, j as i64, &threshold),
+                (i..j)
+                    .rev()
+                    .find(|x| reference[*x] <= threshold)
+                    .map(|x| x as i64)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_seg_shared_readers() {
+    const M: usize = 1000;
+    let mut seg = SegMax::new(0, M as i64);
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M as i64);
+    let distr2 = Uniform::from(0..M as i64 + 1);
+    let distr3 = Uniform::from(-10..11);
+
+    let mut reference = vec![0; M];
+    seg.assign(0, M as i64, &0);
+    for _ in 0..300 {
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let val: i64 = distr3.sample(&mut g);
+        if distr.sample(&mut g) % 2 == 0 {
+            seg.add(a, b, &val);
+            for h in a..b {
+                reference[h as usize] += val;
+            }
+        } else {
+            seg.chmax(a, b, &val);
+            for h in a..b {
+                reference[h as usize] = max(reference[h as usize], val);
+            }
+        }
+    }
+
+    let stats = seg.mem_stats();
+    let seg = Arc::new(seg);
+    let reference = Arc::new(reference);
+    let handles = (0..4)
+        .map(|_| {
+            let (seg, reference) = (seg.clone(), reference.clone());
+            thread::spawn(move || {
+                let mut g = thread_rng();
+                for _ in 0..2000 {
+                    let a = distr.sample(&mut g) as usize;
+                    let b = max(distr2.sample(&mut g) as usize, a + 1);
+                    let expect = reference[a..b].iter().max().cloned().unwrap();
+                    assert_eq!(seg.query_range(a as i64, b as i64), expect);
+                    let pos = (a..b).find(|x| reference[*x] == expect).unwrap();
+                    assert_eq!(
+                        seg.query_range_arg(a as i64, b as i64),
+                        (pos as i64, expect)
+                    );
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert_eq!(seg.mem_stats(), stats);
+}
+
+#[test]
+fn test_seg_from_slice() {
+    const M: usize = 100;
+    const OFFSET: i64 = 1000;
+    let mut g = thread_rng();
+    let distr = Uniform::from(-10..11);
+    let mut reference = (0..M).map(|_| distr.sample(&mut g)).collect::<Vec<i64>>();
+    let mut seg_max = SegMax::from_slice(OFFSET, &reference);
+    let mut seg_min = SegMin::from_iter(OFFSET, reference.iter().cloned());
+    assert_eq!(seg_max.to_vec(), reference);
+    assert_eq!(seg_min.to_vec(), reference);
+    for i in 0..M {
+        for j in i + 1..M + 1 {
+            let (l, r) = (i as i64 + OFFSET, j as i64 + OFFSET);
+            assert_eq!(
+                seg_max.query_range(l, r),
+                *reference[i..j].iter().max().unwrap()
+            );
+            assert_eq!(
+                seg_min.query_range(l, r),
+                *reference[i..j].iter().min().unwrap()
+            );
+        }
+    }
+
+    let distr_idx = Uniform::from(0..M + 1);
+    for _ in 0..200 {
+        let a = distr_idx.sample(&mut g);
+        let b = max(distr_idx.sample(&mut g), a);
+        let val: i64 = distr.sample(&mut g);
+        let (l, r) = (a as i64 + OFFSET, b as i64 + OFFSET);
+        match val.rem_euclid(3) {
+            0 => {
+                seg_max.add(l, r, &val);
+                seg_min.add(l, r, &val);
+                for x in reference[a..b].iter_mut() {
+                    *x += val;
+                }
+            }
+            1 => {
+                seg_max.assign(l, r, &val);
+                seg_min.assign(l, r, &val);
+                for x in reference[a..b].iter_mut() {
+                    *x = val;
+                }
+            }
+            _ => {
+                seg_max.chmin(l, r, &val);
+                seg_min.chmin(l, r, &val);
+                for x in reference[a..b].iter_mut() {
+                    *x = min(*x, val);
+                }
+            }
+        }
+        assert_eq!(seg_max.to_vec(), reference);
+        assert_eq!(seg_min.to_vec(), reference);
+    }
+}
@@ -3,11 +3,32 @@ use rand::distributions::{Distribution, Uniform};
 #[cfg(test)]
 use rand::thread_rng;
 
-use std::cmp::*;
 #[cfg(test)]
-use std::collections::HashMap;
+use std::cmp::*;
 use std::fmt::Debug;
 use std::ops::{Add, Mul};
+#[cfg(test)]
+use std::sync::Arc;
+#[cfg(test)]
+use std::thread;
+
+use seglazy::{Action, MemStats, Monoid, SegLazy};
+
+/// sum of elements
+#[derive(Debug, Clone, Copy)]
+pub struct OpSum;
+
+impl<T> Monoid<T> for OpSum
+where
+    T: Add<Output = T> + Default + Clone,
+{
+    fn identity() -> T {
+        Default::default()
+    }
+    fn combine(a: &T, b: &T) -> T {
+        a.clone() + b.clone()
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 enum Change<T>
@@ -18,298 +39,224 @@ where
     Rel(T),
 }
 
+impl<T> Action<T> for Change<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    fn apply_len(&self, agg: &T, len: i64) -> T {
+        match self {
+            Change::Abs(v) => v.clone() * len,
+            Change::Rel(v) => agg.clone() + v.clone() * len,
+        }
+    }
+    fn compose(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Change::Abs(x), Change::Rel(y)) => Change::Abs(x.clone() + y.clone()),
+            (Change::Rel(x), Change::Rel(y)) => Change::Rel(x.clone() + y.clone()),
+            (_, y) => y.clone(),
+        }
+    }
+    fn overrides(&self) -> bool {
+        matches!(self, Change::Abs(_))
+    }
+}
+
 #[derive(Debug, Clone)]
-struct N<T>
+pub struct SegSum<T>
 where
     T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
 {
-    l: i64,
-    r: i64,
-    s: T,
-    left: Option<Box<N<T>>>,
-    right: Option<Box<N<T>>>,
-    mark: Option<Change<T>>, //lazy
+    seg: SegLazy<T, OpSum, Change<T>>,
 }
 
-impl<T> N<T>
+impl<T> SegSum<T>
 where
     T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
 {
     pub fn new(l: i64, r: i64) -> Self {
         Self {
-            l: l,
-            r: r,
-            s: Default::default(),
-            left: None,
-            right: None,
-            mark: None,
+            seg: SegLazy::new(l, r),
         }
     }
-    fn set_lazy_abs(&mut self, val: T) {
-        self.mark = Some(Change::Abs(val.clone()));
-        self.s = val;
-    }
-    fn set_lazy_rel(&mut self, val: T) {
-        match &self.mark {
-            Some(Change::Abs(_)) => {
-                self.push_down_abs();
-                assert!(self.mark.is_none());
-            }
-            _ => {}
-        }
-        match &mut self.mark {
-            Some(Change::Rel(ref mut v)) => {
-                *v = v.clone() + val;
-            }
-            _ => {
-                self.mark = Some(Change::Rel(val));
-            }
+    /// reserves space for n nodes
+    pub fn with_capacity(l: i64, r: i64, n: usize) -> Self {
+        Self {
+            seg: SegLazy::with_capacity(l, r, n),
         }
     }
-    pub fn range_sum(&self) -> T {
-        match &self.mark {
-            Some(Change::Abs(v)) => v.clone() * (self.r - self.l),
-            Some(Change::Rel(v)) => self.s.clone() + v.clone() * (self.r - self.l),
-            _ => self.s.clone(),
+    /// see SegLazy::from_slice
+    pub fn from_slice(offset: i64, vals: &[T]) -> Self {
+        Self {
+            seg: SegLazy::from_slice(offset, vals),
         }
     }
-    fn extend(&mut self) {
-        if self.l + 1 >= self.r {
-            return;
-        }
-        self.extend_left();
-        self.extend_right();
-    }
-    fn extend_left(&mut self) {
-        let m = (self.l + self.r) / 2;
-        if self.l < self.r && self.l < m {
-            match self.left {
-                None => {
-                    self.left = Some(Box::new(N::new(self.l, m)));
-                }
-                _ => {}
-            }
+    /// see SegLazy::from_iter
+    pub fn from_iter<I>(offset: i64, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Self {
+            seg: SegLazy::from_iter(offset, items),
         }
     }
-    fn extend_right(&mut self) {
-        let m = (self.l + self.r) / 2;
-        if self.l < self.r && m < self.r {
-            match self.right {
-                None => {
-                    self.right = Some(Box::new(N::new(m, self.r)));
-                }
-                _ => {}
-            }
-        }
+    /// returns the elements of the whole range in index order in O(n)
+    pub fn to_vec(&self) -> Vec<T> {
+        self.seg.to_vec()
     }
-    fn push_down_abs(&mut self) {
-        use std::mem::swap;
-        match &mut self.mark {
-            Some(Change::Abs(ref mut a)) => {
-                let mut x = Default::default();
-                swap(&mut x, a);
-                self.mark = None;
-                self.extend();
-                if let Some(ref mut y) = &mut self.left {
-                    y.set_lazy_abs(x.clone());
-                }
-                if let Some(ref mut y) = &mut self.right {
-                    y.set_lazy_abs(x.clone());
-                }
-                self.s = x * (self.r - self.l);
-            }
-            _ => {}
-        }
+    pub fn mem_stats(&self) -> MemStats {
+        self.seg.mem_stats()
     }
-    fn push_down_rel(&mut self) {
-        use std::mem::swap;
-        match &mut self.mark {
-            Some(Change::Rel(ref mut a)) => {
-                let mut x = Default::default();
-                swap(&mut x, a);
-                self.mark = None;
-                self.extend();
-                if let Some(ref mut y) = &mut self.left {
-                    y.set_lazy_rel(x.clone());
-                }
-                if let Some(ref mut y) = &mut self.right {
-                    y.set_lazy_rel(x.clone());
-                }
-                self.s = self.s.clone() + x * (self.r - self.l);
-            }
-            _ => {}
-        }
+    pub fn add(&mut self, l: i64, r: i64, delta: &T) {
+        self.seg.apply_range(l, r, &Change::Rel(delta.clone()));
     }
-    fn push_down(&mut self) {
-        if let Some(ref x) = &self.mark {
-            match x {
-                Change::Abs(_) => {
-                    self.push_down_abs();
-                }
-                _ => {
-                    self.push_down_rel();
-                }
-            }
-        }
+    pub fn query_range(&self, l: i64, r: i64) -> T {
+        self.seg.query_range(l, r)
     }
-    pub fn update(&mut self, ll: i64, rr: i64, val: &T) {
-        if ll <= self.l && self.r <= rr {
-            self.set_lazy_abs(val.clone());
-        } else {
-            self.push_down();
-            let m = (self.l + self.r) / 2;
-            if ll < m {
-                self.extend_left();
-                match &mut self.left {
-                    Some(ref mut x) => {
-                        x.update(ll, min(m, rr), val);
-                    }
-                    _ => {}
-                }
-            }
-            if m < rr {
-                self.extend_right();
-                match &mut self.right {
-                    Some(ref mut x) => {
-                        x.update(max(m, ll), rr, val);
-                    }
-                    _ => {}
-                }
-            }
-            self.s = self
-                .left
-                .as_mut()
-                .map_or(Default::default(), |x| x.range_sum())
-                + self
-                    .right
-                    .as_mut()
-                    .map_or(Default::default(), |x| x.range_sum());
-            debug_assert!(self.mark.is_none());
-        }
+    pub fn update(&mut self, l: i64, r: i64, val: &T) {
+        self.seg.apply_range(l, r, &Change::Abs(val.clone()));
     }
-    pub fn add(&mut self, ll: i64, rr: i64, delta: &T) {
-        if ll <= self.l && self.r <= rr {
-            self.set_lazy_rel(delta.clone());
-        } else {
-            self.push_down();
-            let m = (self.l + self.r) / 2;
-            if ll < m {
-                self.extend();
-                if let Some(ref mut x) = &mut self.left {
-                    x.add(ll, min(m, rr), delta);
-                }
-            }
-            if m < rr {
-                self.extend();
-                if let Some(ref mut x) = &mut self.right {
-                    x.add(max(m, ll), rr, delta);
-                }
-            }
-            self.s = self
-                .left
-                .as_mut()
-                .map_or(Default::default(), |x| x.range_sum())
-                + self
-                    .right
-                    .as_mut()
-                    .map_or(Default::default(), |x| x.range_sum());
-            debug_assert!(self.mark.is_none());
-        }
+    /// returns the highest r such that pred(sum of [l,r)) holds, see SegLazy::max_right
+    pub fn max_right<P>(&self, l: i64, pred: P) -> i64
+    where
+        P: Fn(&T) -> bool,
+    {
+        self.seg.max_right(l, pred)
+    }
+    /// returns the lowest l such that pred(sum of [l,r)) holds, see SegLazy::min_left
+    pub fn min_left<P>(&self, r: i64, pred: P) -> i64
+    where
+        P: Fn(&T) -> bool,
+    {
+        self.seg.min_left(r, pred)
     }
-    pub fn query_sum(&mut self, ll: i64, rr: i64) -> T {
-        if ll >= rr || max(ll, self.l) >= min(rr, self.r) {
-            Default::default()
-        } else if ll <= self.l && self.r <= rr {
-            self.range_sum()
+    /// returns the smallest r such that sum of [l,r) >= target in O(log n),
+    /// elements in [l,lim_r) have to be non-negative
+    pub fn lower_bound_prefix(&self, l: i64, target: &T) -> Option<i64>
+    where
+        T: PartialOrd,
+    {
+        if T::default() >= *target {
+            return Some(l);
+        }
+        let r = self.seg.max_right(l, |x| *x < *target);
+        if r < self.seg.bounds().1 {
+            Some(r + 1)
         } else {
-            self.push_down();
-            let mut ret: T = Default::default();
-            let m = (self.l + self.r) / 2;
-            if ll < m {
-                if let Some(ref mut x) = &mut self.left {
-                    ret = ret + x.query_sum(ll, min(m, rr));
-                }
-            }
-            if m < rr {
-                if let Some(ref mut x) = &mut self.right {
-                    ret = ret + x.query_sum(max(m, ll), rr);
-                }
-            }
-            ret
+            None
         }
     }
     #[cfg(test)]
-    pub fn dbg(&self, d_parent: i64, depths: &mut HashMap<i64, i64>) -> usize {
-        *depths.entry(d_parent).or_default() += 1;
-        let mut count = 0;
-        if let Some(ref x) = &self.left {
-            count += x.dbg(d_parent + 1, depths);
+    pub fn dbg(&self) {
+        self.seg.dbg();
+    }
+}
+
+/// element x becomes a*x + b for Affine(a, b)
+#[derive(Debug, Clone, Copy)]
+enum ChangeAffine<T>
+where
+    T: Add<Output = T> + Mul<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    Abs(T),
+    Rel(T),
+    Affine(T, T),
+}
+
+impl<T> Action<T> for ChangeAffine<T>
+where
+    T: Add<Output = T> + Mul<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    fn apply_len(&self, agg: &T, len: i64) -> T {
+        match self {
+            ChangeAffine::Abs(v) => v.clone() * len,
+            ChangeAffine::Rel(v) => agg.clone() + v.clone() * len,
+            ChangeAffine::Affine(a, b) => a.clone() * agg.clone() + b.clone() * len,
         }
-        println!(
-            "[{},{}), mark: {:?}, s: {:?}",
-            self.l, self.r, self.mark, self.s
-        );
-        if let Some(ref x) = &self.right {
-            count += x.dbg(d_parent + 1, depths);
+    }
+    fn compose(&self, other: &Self) -> Self {
+        use self::ChangeAffine::*;
+        match (self, other) {
+            (Abs(x), Rel(y)) => Abs(x.clone() + y.clone()),
+            (Abs(x), Affine(a, b)) => Abs(a.clone() * x.clone() + b.clone()),
+            (Rel(x), Rel(y)) => Rel(x.clone() + y.clone()),
+            (Rel(x), Affine(a, b)) => Affine(a.clone(), a.clone() * x.clone() + b.clone()),
+            (Affine(a, b), Rel(y)) => Affine(a.clone(), b.clone() + y.clone()),
+            (Affine(a1, b1), Affine(a2, b2)) => Affine(
+                a2.clone() * a1.clone(),
+                a2.clone() * b1.clone() + b2.clone(),
+            ),
+            (_, y) => y.clone(),
         }
-        count + 1
+    }
+    fn overrides(&self) -> bool {
+        matches!(self, ChangeAffine::Abs(_))
     }
 }
 
+/// summation segment tree supporting range affine updates in addition to add and assign
 #[derive(Debug, Clone)]
-pub struct SegSum<T>
+pub struct SegSumAffine<T>
 where
-    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+    T: Add<Output = T> + Mul<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
 {
-    lim_l: i64,
-    lim_r: i64,
-    r: N<T>,
+    seg: SegLazy<T, OpSum, ChangeAffine<T>>,
 }
 
-impl<T> SegSum<T>
+impl<T> SegSumAffine<T>
 where
-    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+    T: Add<Output = T> + Mul<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
 {
     pub fn new(l: i64, r: i64) -> Self {
         Self {
-            lim_l: l,
-            lim_r: r,
-            r: N::new(l, r),
+            seg: SegLazy::new(l, r),
         }
     }
-    pub fn add(&mut self, l: i64, r: i64, delta: &T) {
-        if l < r {
-            assert!(self.lim_l <= l);
-            assert!(r <= self.lim_r);
-            self.r.add(l, r, delta);
+    /// reserves space for n nodes
+    pub fn with_capacity(l: i64, r: i64, n: usize) -> Self {
+        Self {
+            seg: SegLazy::with_capacity(l, r, n),
         }
     }
-    pub fn query_range(&mut self, l: i64, r: i64) -> T {
-        assert!(self.lim_l <= l);
-        assert!(r <= self.lim_r);
-        self.r.query_sum(l, r)
+    /// see SegLazy::from_slice
+    pub fn from_slice(offset: i64, vals: &[T]) -> Self {
+        Self {
+            seg: SegLazy::from_slice(offset, vals),
+        }
     }
-    pub fn update(&mut self, l: i64, r: i64, val: &T) {
-        if l < r {
-            assert!(self.lim_l <= l);
-            assert!(r <= self.lim_r);
-            self.r.update(l, r, val);
+    /// see SegLazy::from_iter
+    pub fn from_iter<I>(offset: i64, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Self {
+            seg: SegLazy::from_iter(offset, items),
         }
     }
+    /// returns the elements of the whole range in index order in O(n)
+    pub fn to_vec(&self) -> Vec<T> {
+        self.seg.to_vec()
+    }
+    pub fn mem_stats(&self) -> MemStats {
+        self.seg.mem_stats()
+    }
+    pub fn add(&mut self, l: i64, r: i64, delta: &T) {
+        self.seg
+            .apply_range(l, r, &ChangeAffine::Rel(delta.clone()));
+    }
+    pub fn update(&mut self, l: i64, r: i64, val: &T) {
+        self.seg.apply_range(l, r, &ChangeAffine::Abs(val.clone()));
+    }
+    /// sets elements x in [l,r) to a*x + b
+    pub fn affine(&mut self, l: i64, r: i64, a: &T, b: &T) {
+        let f = ChangeAffine::Affine(a.clone(), b.clone());
+        self.seg.apply_range(l, r, &f);
+    }
+    pub fn query_range(&self, l: i64, r: i64) -> T {
+        self.seg.query_range(l, r)
+    }
     #[cfg(test)]
     pub fn dbg(&self) {
-        let mut count = 0;
-        let mut depths = HashMap::new();
-        self.r.dbg(0, &mut depths);
-        let mut d_avg = 0.;
-        for (depth, freq) in depths {
-            println!("depth: {}, count: {}", depth, freq);
-            d_avg += (depth * freq) as f64;
-            count += freq;
-        }
-        d_avg /= count as f64;
-        println!("avg depth per node: {}", d_avg);
-        println!("count nodes: {}", count);
+        self.seg.dbg();
     }
 }
 
@@ -363,3 +310,249 @@ fn test_seg() {
         }
     }
 }
+
+#[test]
+fn test_seg_prefix_search() {
+    const M: usize = 64;
+    let mut seg = SegSum::new(0, M as i64);
+    let mut reference = vec![0; M];
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M as i64);
+    let distr2 = Uniform::from(0..M as i64 + 1);
+    let distr3 = Uniform::from(0..11);
+    for _ in 0..500 {
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let val: i64 = distr3.sample(&mut g);
+        if distr.sample(&mut g) % 2 == 0 {
+            seg.add(a, b, &val);
+            for j in a..b {
+                reference[j as usize] += val;
+            }
+        } else {
+            seg.update(a, b, &val);
+            for j in a..b {
+                reference[j as usize] = val;
+            }
+        }
+
+        let l = distr2.sample(&mut g) as usize;
+        let target: i64 = distr.sample(&mut g) * 5;
+        let expect = (l..M + 1)
+            .find(|r| reference[l..*r].iter().sum::<i64>() >= target)
+            .map(|r| r as i64);
+        assert_eq!(seg.lower_bound_prefix(l as i64, &target), expect);
+
+        let expect = (l..M + 1)
+            .rev()
+            .find(|r| reference[l..*r].iter().sum::<i64>() <= target)
+            .unwrap() as i64;
+        assert_eq!(seg.max_right(l as i64, |x| *x <= target), expect);
+
+        let r = l;
+        let expect = (0..r + 1)
+            .find(|l| reference[*l..r].iter().sum::<i64>() <= target)
+            .unwrap() as i64;
+        assert_eq!(seg.min_left(r as i64, |x| *x <= target), expect);
+    }
+}
+
+#[test]
+fn test_seg_shared_readers() {
+    const M: usize = 1000;
+    let mut seg = SegSum::new(0, M as i64);
+    let mut reference = vec![0i64; M];
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M as i64);
+    let distr2 = Uniform::from(0..M as i64 + 1);
+    let distr3 = Uniform::from(0..11);
+    for _ in 0..500 {
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let val: i64 = distr3.sample(&mut g);
+        if distr.sample(&mut g) % 2 == 0 {
+            seg.add(a, b, &val);
+            for j in a..b {
+                reference[j as usize] += val;
+            }
+        } else {
+            seg.update(a, b, &val);
+            for j in a..b {
+                reference[j as usize] = val;
+            }
+        }
+    }
+
+    //queries leave pending marks in place
+    let stats = seg.mem_stats();
+    let seg = Arc::new(seg);
+    let reference = Arc::new(reference);
+    let handles = (0..4)
+        .map(|_| {
+            let (seg, reference) = (seg.clone(), reference.clone());
+            thread::spawn(move || {
+                let mut g = thread_rng();
+                for _ in 0..2000 {
+                    let a = distr.sample(&mut g) as usize;
+                    let b = max(distr2.sample(&mut g) as usize, a);
+                    let expect = reference[a..b].iter().sum::<i64>();
+                    assert_eq!(seg.query_range(a as i64, b as i64), expect);
+                    let target = expect;
+                    let r = seg.lower_bound_prefix(a as i64, &target).unwrap();
+                    assert!(reference[a..r as usize].iter().sum::<i64>() >= target);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert_eq!(seg.mem_stats(), stats);
+}
+
+#[test]
+fn test_seg_from_slice() {
+    const M: usize = 100;
+    const OFFSET: i64 = -37;
+    let mut g = thread_rng();
+    let distr = Uniform::from(-10..11);
+    let mut reference = (0..M).map(|_| distr.sample(&mut g)).collect::<Vec<i64>>();
+    let mut seg = SegSum::from_slice(OFFSET, &reference);
+    assert_eq!(seg.mem_stats().live_nodes, 2 * M - 1);
+    assert_eq!(seg.to_vec(), reference);
+    for i in 0..M {
+        for j in i..M + 1 {
+            assert_eq!(
+                seg.query_range(i as i64 + OFFSET, j as i64 + OFFSET),
+                reference[i..j].iter().sum::<i64>()
+            );
+        }
+    }
+
+    let distr_idx = Uniform::from(0..M + 1);
+    for _ in 0..200 {
+        let a = distr_idx.sample(&mut g);
+        let b = max(distr_idx.sample(&mut g), a);
+        let val: i64 = distr.sample(&mut g);
+        if val % 2 == 0 {
+            seg.add(a as i64 + OFFSET, b as i64 + OFFSET, &val);
+            for x in reference[a..b].iter_mut() {
+                *x += val;
+            }
+        } else {
+            seg.update(a as i64 + OFFSET, b as i64 + OFFSET, &val);
+            for x in reference[a..b].iter_mut() {
+                *x = val;
+            }
+        }
+        assert_eq!(seg.to_vec(), reference);
+    }
+
+    let seg = SegSum::from_iter(5, (0..10).map(|x| x * x));
+    assert_eq!(seg.query_range(5, 15), 285);
+    assert_eq!(seg.query_range(7, 9), 4 + 9);
+    let seg = SegSum::<i64>::from_iter(0, vec![]);
+    assert!(seg.to_vec().is_empty());
+
+    //untouched elements of a sparse tree are the default
+    let mut seg = SegSum::new(0, 6);
+    seg.add(2, 4, &3);
+    seg.update(3, 6, &1);
+    assert_eq!(seg.to_vec(), vec![0, 0, 3, 1, 1, 1]);
+    let mut seg = SegSumAffine::from_slice(0, &[1, 2, 3, 4]);
+    seg.affine(1, 4, &2, &1);
+    assert_eq!(seg.to_vec(), vec![1, 5, 7, 9]);
+}
+
+/// integers modulo a prime for tests of SegSumAffine
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ModP(i64);
+
+#[cfg(test)]
+const P: i64 = 1_000_000_007;
+
+#[cfg(test)]
+impl Add for ModP {
+    type Output = ModP;
+    fn add(self, other: ModP) -> ModP {
+        ModP((self.0 + other.0) % P)
+    }
+}
+
+#[cfg(test)]
+impl Mul for ModP {
+    type Output = ModP;
+    fn mul(self, other: ModP) -> ModP {
+        ModP(self.0 * other.0 % P)
+    }
+}
+
+#[cfg(test)]
+impl Mul<i64> for ModP {
+    type Output = ModP;
+    fn mul(self, other: i64) -> ModP {
+        ModP(self.0 * (other.rem_euclid(P)) % P)
+    }
+}
+
+#[test]
+fn test_seg_affine() {
+    const M: usize = 64;
+    let mut seg = SegSumAffine::new(0, M as i64);
+    let mut reference = vec![ModP(0); M];
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M as i64);
+    let distr2 = Uniform::from(0..M as i64 + 1);
+    let distr3 = Uniform::from(0..P);
+    for _ in 0..5000 {
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let x = ModP(distr3.sample(&mut g));
+        let y = ModP(distr3.sample(&mut g));
+        match distr.sample(&mut g) % 3 {
+            0 => {
+                seg.add(a, b, &x);
+                for j in a..b {
+                    reference[j as usize] = reference[j as usize] + x;
+                }
+            }
+            1 => {
+                seg.update(a, b, &x);
+                for j in a..b {
+                    reference[j as usize] = x;
+                }
+            }
+            _ => {
+                seg.affine(a, b, &x, &y);
+                for j in a..b {
+                    reference[j as usize] = x * reference[j as usize] + y;
+                }
+            }
+        }
+
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let expect = reference[a as usize..b as usize]
+            .iter()
+            .fold(ModP(0), |acc, v| acc + *v);
+        assert_eq!(seg.query_range(a, b), expect);
+    }
+
+    for i in 0..M {
+        for j in i..M + 1 {
+            let expect = reference[i..j].iter().fold(ModP(0), |acc, v| acc + *v);
+            assert_eq!(seg.query_range(i as i64, j as i64), expect);
+        }
+    }
+
+    //plain integers
+    let mut seg = SegSumAffine::new(0, 10);
+    seg.update(0, 10, &1i64);
+    seg.affine(0, 5, &3, &2);
+    seg.add(4, 10, &1);
+    seg.affine(3, 7, &-1, &0);
+    //5 5 5 -5 -6 -2 -2 2 2 2
+    assert_eq!(seg.query_range(0, 10), 6);
+    assert_eq!(seg.query_range(3, 7), -15);
+}
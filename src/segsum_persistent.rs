@@ -0,0 +1,533 @@
+//! persistent dynamic summation segment tree
+//!
+//! every update copies the nodes on the modified paths and returns a new root,
+//! so previous versions remain valid and share their untouched subtrees with newer versions;
+//! range add marks are never pushed down by adds but are accumulated by queries instead,
+//! assign marks replace the subtree and are pushed into copies of the children when a later update passes through
+
+#[cfg(test)]
+use rand::distributions::{Distribution, Uniform};
+#[cfg(test)]
+use rand::thread_rng;
+
+use std::cmp::*;
+use std::fmt::Debug;
+use std::ops::{Add, Mul};
+use std::rc::Rc;
+
+type Link<T> = Option<Rc<N<T>>>;
+
+#[derive(Debug, Clone)]
+struct N<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    s: T,              //sum of the subtree including marks of the subtree
+    add: T,            //added to all elements of the subtree, not pushed down by adds
+    assign: Option<T>, //all elements of the subtree are this plus add, children are unused
+    left: Link<T>,
+    right: Link<T>,
+}
+
+///helper function
+fn sum<T>(n: &Link<T>) -> T
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    n.as_ref().map_or(Default::default(), |x| x.s.clone())
+}
+
+///helper function: midpoint without overflow
+fn mid(l: i64, r: i64) -> i64 {
+    l + (r - l) / 2
+}
+
+///helper function: returns a copy of the subtree of [l,r) with delta added to all of it
+fn add_all<T>(n: &Link<T>, l: i64, r: i64, delta: &T) -> Link<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    Some(Rc::new(match n {
+        Some(x) => N {
+            s: x.s.clone() + delta.clone() * (r - l),
+            add: x.add.clone() + delta.clone(),
+            assign: x.assign.clone(),
+            left: x.left.clone(),
+            right: x.right.clone(),
+        },
+        None => N {
+            s: delta.clone() * (r - l),
+            add: delta.clone(),
+            assign: None,
+            left: None,
+            right: None,
+        },
+    }))
+}
+
+///helper function: returns a new subtree of [l,r) with all elements set to val
+fn assign_all<T>(l: i64, r: i64, val: &T) -> Link<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    Some(Rc::new(N {
+        s: val.clone() * (r - l),
+        add: Default::default(),
+        assign: Some(val.clone()),
+        left: None,
+        right: None,
+    }))
+}
+
+///helper function: returns (add, left, right) of n over [l,r) with a pending assign turned into new children
+fn open<T>(n: &Link<T>, l: i64, r: i64) -> (T, Link<T>, Link<T>)
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    let m = mid(l, r);
+    match n {
+        Some(x) => match x.assign {
+            Some(ref v) => (x.add.clone(), assign_all(l, m, v), assign_all(m, r, v)),
+            None => (x.add.clone(), x.left.clone(), x.right.clone()),
+        },
+        None => (Default::default(), None, None),
+    }
+}
+
+/// returns a copy of the subtree of [l,r) with delta added to [ll,rr)
+fn add<T>(n: &Link<T>, l: i64, r: i64, ll: i64, rr: i64, delta: &T) -> Link<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    if max(ll, l) >= min(rr, r) {
+        return n.clone();
+    }
+    if ll <= l && r <= rr {
+        return add_all(n, l, r, delta);
+    }
+    let m = mid(l, r);
+    let (a, left, right) = open(n, l, r);
+    let left = add(&left, l, m, ll, rr, delta);
+    let right = add(&right, m, r, ll, rr, delta);
+    Some(Rc::new(N {
+        s: sum(&left) + sum(&right) + a.clone() * (r - l),
+        add: a,
+        assign: None,
+        left,
+        right,
+    }))
+}
+
+/// returns a copy of the subtree of [l,r) with [ll,rr) set to val,
+/// add marks on the path are pushed into copies of the children
+fn update<T>(n: &Link<T>, l: i64, r: i64, ll: i64, rr: i64, val: &T) -> Link<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    if max(ll, l) >= min(rr, r) {
+        return n.clone();
+    }
+    if ll <= l && r <= rr {
+        return assign_all(l, r, val);
+    }
+    let m = mid(l, r);
+    let (a, left, right) = open(n, l, r);
+    let left = update(&add_all(&left, l, m, &a), l, m, ll, rr, val);
+    let right = update(&add_all(&right, m, r, &a), m, r, ll, rr, val);
+    Some(Rc::new(N {
+        s: sum(&left) + sum(&right),
+        add: Default::default(),
+        assign: None,
+        left,
+        right,
+    }))
+}
+
+/// returns sum of [ll,rr) within the subtree of [l,r), acc is the sum of marks of the ancestors
+fn query<T>(n: &Link<T>, l: i64, r: i64, ll: i64, rr: i64, acc: &T) -> T
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    let (a, b) = (max(ll, l), min(rr, r));
+    if a >= b {
+        return Default::default();
+    }
+    match n {
+        None => acc.clone() * (b - a),
+        Some(x) if ll <= l && r <= rr => x.s.clone() + acc.clone() * (r - l),
+        Some(x) => {
+            let acc = acc.clone() + x.add.clone();
+            match x.assign {
+                Some(ref v) => (v.clone() + acc) * (b - a),
+                None => {
+                    let m = mid(l, r);
+                    query(&x.left, l, m, ll, rr, &acc) + query(&x.right, m, r, ll, rr, &acc)
+                }
+            }
+        }
+    }
+}
+
+/// a version of the tree, cloning is O(1)
+#[derive(Debug, Clone)]
+pub struct Version<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    lim_l: i64,
+    lim_r: i64,
+    root: Link<T>,
+}
+
+impl<T> Version<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    /// panics if the length r - l does not fit in i64
+    pub fn new(l: i64, r: i64) -> Self {
+        assert!(r.checked_sub(l).is_some(), "range length has to fit in i64");
+        Self {
+            lim_l: l,
+            lim_r: r,
+            root: None,
+        }
+    }
+    /// returns a new version with delta added to [l,r) in O(log n)
+    pub fn add(&self, l: i64, r: i64, delta: &T) -> Self {
+        if l >= r {
+            return self.clone();
+        }
+        assert!(self.lim_l <= l);
+        assert!(r <= self.lim_r);
+        Self {
+            lim_l: self.lim_l,
+            lim_r: self.lim_r,
+            root: add(&self.root, self.lim_l, self.lim_r, l, r, delta),
+        }
+    }
+    /// returns a new version with [l,r) set to val in O(log n)
+    pub fn update(&self, l: i64, r: i64, val: &T) -> Self {
+        if l >= r {
+            return self.clone();
+        }
+        assert!(self.lim_l <= l);
+        assert!(r <= self.lim_r);
+        Self {
+            lim_l: self.lim_l,
+            lim_r: self.lim_r,
+            root: update(&self.root, self.lim_l, self.lim_r, l, r, val),
+        }
+    }
+    /// returns sum of [l,r) in O(log n)
+    pub fn query_range(&self, l: i64, r: i64) -> T {
+        assert!(self.lim_l <= l);
+        assert!(r <= self.lim_r);
+        query(
+            &self.root,
+            self.lim_l,
+            self.lim_r,
+            l,
+            r,
+            &Default::default(),
+        )
+    }
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// linear history of versions, version 0 is the initial tree of default elements
+#[derive(Debug, Clone)]
+pub struct SegSumPersistent<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    versions: Vec<Version<T>>,
+}
+
+impl<T> SegSumPersistent<T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    pub fn new(l: i64, r: i64) -> Self {
+        Self {
+            versions: vec![Version::new(l, r)],
+        }
+    }
+    /// adds delta to [l,r) and returns the number of the new version
+    pub fn add(&mut self, l: i64, r: i64, delta: &T) -> usize {
+        let v = self.versions.last().unwrap().add(l, r, delta);
+        self.versions.push(v);
+        self.latest()
+    }
+    /// sets [l,r) to val and returns the number of the new version
+    pub fn update(&mut self, l: i64, r: i64, val: &T) -> usize {
+        let v = self.versions.last().unwrap().update(l, r, val);
+        self.versions.push(v);
+        self.latest()
+    }
+    /// returns sum of [l,r) of the latest version
+    pub fn query_range(&self, l: i64, r: i64) -> T {
+        self.versions.last().unwrap().query_range(l, r)
+    }
+    /// returns sum of [l,r) as of version n
+    pub fn query_range_at(&self, n: usize, l: i64, r: i64) -> T {
+        self.versions[n].query_range(l, r)
+    }
+    /// returns handle to version n
+    pub fn version(&self, n: usize) -> Version<T> {
+        self.versions[n].clone()
+    }
+    /// returns number of the latest version
+    pub fn latest(&self) -> usize {
+        self.versions.len() - 1
+    }
+}
+
+///helper function: subtree of a version with the add marks of its ancestors,
+///or a range of equal counts below an assign mark
+enum View<'a, T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone,
+{
+    Node(&'a Link<T>, i64),
+    Uniform(i64),
+}
+
+impl<'a, T> View<'a, T>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone + Into<i64>,
+{
+    fn count(&self, len: i64) -> i64 {
+        match *self {
+            View::Node(n, acc) => sum(n).into() + acc * len,
+            View::Uniform(c) => c * len,
+        }
+    }
+    fn children(&self) -> (Self, Self) {
+        match *self {
+            View::Node(Some(x), acc) => {
+                let acc = acc + x.add.clone().into();
+                match x.assign {
+                    Some(ref v) => {
+                        let c = v.clone().into() + acc;
+                        (View::Uniform(c), View::Uniform(c))
+                    }
+                    None => (View::Node(&x.left, acc), View::Node(&x.right, acc)),
+                }
+            }
+            View::Node(None, acc) => (View::Uniform(acc), View::Uniform(acc)),
+            View::Uniform(c) => (View::Uniform(c), View::Uniform(c)),
+        }
+    }
+}
+
+///helper function: descends version b minus version a
+fn kth<T>(a: View<T>, b: View<T>, l: i64, r: i64, mut k: i64) -> Option<i64>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone + Into<i64>,
+{
+    if l + 1 >= r {
+        return Some(l);
+    }
+    let m = mid(l, r);
+    let (a_l, a_r) = a.children();
+    let (b_l, b_r) = b.children();
+    let cnt_left = b_l.count(m - l) - a_l.count(m - l);
+    if k < cnt_left {
+        kth(a_l, b_l, l, m, k)
+    } else {
+        k -= cnt_left;
+        kth(a_r, b_r, m, r, k)
+    }
+}
+
+/// treats elements as counts and returns the index holding the k-th (starting from 0) unit
+/// of version b minus version a in O(log n), or None if there are not more than k units;
+/// counts of b have to be at least those of a
+pub fn kth_in_diff<T>(a: &Version<T>, b: &Version<T>, k: i64) -> Option<i64>
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Default + Debug + Clone + Into<i64>,
+{
+    assert_eq!((a.lim_l, a.lim_r), (b.lim_l, b.lim_r));
+    let total = sum(&b.root).into() - sum(&a.root).into();
+    if k < 0 || k >= total {
+        return None;
+    }
+    kth(
+        View::Node(&a.root, 0),
+        View::Node(&b.root, 0),
+        a.lim_l,
+        a.lim_r,
+        k,
+    )
+}
+
+/// answers k-th smallest value of a subarray using one version per prefix of the array
+#[derive(Debug, Clone)]
+pub struct KthInRange {
+    prefix: SegSumPersistent<i64>,
+}
+
+impl KthInRange {
+    /// builds versions in O(n log m) where m is the range of values;
+    /// panics if a value is i64::MAX or max - min + 1 does not fit in i64
+    pub fn new(values: &[i64]) -> Self {
+        let lo = values.iter().cloned().min().unwrap_or(0);
+        let hi = values.iter().cloned().max().unwrap_or(0);
+        let hi = hi
+            .checked_add(1)
+            .filter(|hi| hi.checked_sub(lo).is_some())
+            .expect("range of values has to fit in i64");
+        let mut prefix = SegSumPersistent::new(lo, hi);
+        for v in values.iter() {
+            prefix.add(*v, *v + 1, &1);
+        }
+        Self { prefix }
+    }
+    /// returns the k-th (starting from 0) smallest value of values[l..r)
+    pub fn kth_in_range(&self, l: usize, r: usize, k: usize) -> Option<i64> {
+        assert!(l <= r && r <= self.prefix.latest());
+        kth_in_diff(&self.prefix.version(l), &self.prefix.version(r), k as i64)
+    }
+}
+
+#[test]
+fn test_seg_persistent() {
+    const M: usize = 64;
+    let mut seg = SegSumPersistent::new(0, M as i64);
+    let mut g = thread_rng();
+    let distr = Uniform::from(0..M as i64);
+    let distr2 = Uniform::from(0..M as i64 + 1);
+    let distr3 = Uniform::from(-10..11);
+
+    let mut history = vec![vec![0i64; M]];
+    for _ in 0..200 {
+        let a: i64 = distr.sample(&mut g);
+        let b: i64 = max(distr2.sample(&mut g), a);
+        let val: i64 = distr3.sample(&mut g);
+        let mut reference = history.last().unwrap().clone();
+        let n = if distr.sample(&mut g) % 3 == 0 {
+            for j in a..b {
+                reference[j as usize] = val;
+            }
+            seg.update(a, b, &val)
+        } else {
+            for j in a..b {
+                reference[j as usize] += val;
+            }
+            seg.add(a, b, &val)
+        };
+        history.push(reference);
+        assert_eq!(n, history.len() - 1);
+    }
+
+    for (n, reference) in history.iter().enumerate() {
+        let v = seg.version(n);
+        for i in 0..M {
+            for j in i..M + 1 {
+                let expect = reference[i..j].iter().sum::<i64>();
+                assert_eq!(v.query_range(i as i64, j as i64), expect);
+            }
+        }
+        assert_eq!(
+            seg.query_range_at(n, 0, M as i64),
+            reference.iter().sum::<i64>()
+        );
+    }
+
+    //branching from an old version leaves the history unchanged
+    let old = seg.version(17);
+    let branch = old.add(0, M as i64, &1000);
+    assert_eq!(
+        branch.query_range(0, M as i64),
+        history[17].iter().sum::<i64>() + 1000 * M as i64
+    );
+    assert_eq!(
+        seg.version(17).query_range(0, M as i64),
+        history[17].iter().sum::<i64>()
+    );
+    assert!(old.ptr_eq(&seg.version(17)));
+    assert!(!branch.ptr_eq(&old));
+}
+
+#[test]
+fn test_seg_persistent_kth() {
+    let mut g = thread_rng();
+    let distr = Uniform::from(-1000..1000);
+    let values = (0..300).map(|_| distr.sample(&mut g)).collect::<Vec<i64>>();
+    let t = KthInRange::new(&values);
+
+    let distr_idx = Uniform::from(0..values.len() + 1);
+    for _ in 0..2000 {
+        let a = distr_idx.sample(&mut g);
+        let b = max(a, distr_idx.sample(&mut g));
+        let mut sorted = values[a..b].to_vec();
+        sorted.sort();
+        for k in 0..sorted.len() + 1 {
+            assert_eq!(t.kth_in_range(a, b, k), sorted.get(k).cloned());
+        }
+    }
+
+    //kth over range updates of counts
+    let mut seg = SegSumPersistent::new(0, 100);
+    seg.add(10, 20, &2);
+    seg.add(15, 30, &1);
+    let (v0, v2) = (seg.version(0), seg.version(2));
+    assert_eq!(kth_in_diff(&v0, &v2, 0), Some(10));
+    assert_eq!(kth_in_diff(&v0, &v2, 9), Some(14));
+    assert_eq!(kth_in_diff(&v0, &v2, 10), Some(15));
+    assert_eq!(kth_in_diff(&v0, &v2, 12), Some(15));
+    assert_eq!(kth_in_diff(&v0, &v2, 13), Some(16));
+    assert_eq!(kth_in_diff(&v0, &v2, 34), Some(29));
+    assert_eq!(kth_in_diff(&seg.version(1), &v2, 0), Some(15));
+    assert_eq!(kth_in_diff(&v0, &v2, 35), None);
+
+    //kth over assigned counts
+    let n = seg.update(12, 25, &3);
+    let v3 = seg.version(n);
+    //counts: 10..12: 2, 12..25: 3, 25..30: 1
+    assert_eq!(kth_in_diff(&v0, &v3, 3), Some(11));
+    assert_eq!(kth_in_diff(&v0, &v3, 4), Some(12));
+    assert_eq!(kth_in_diff(&v0, &v3, 42), Some(24));
+    assert_eq!(kth_in_diff(&v0, &v3, 43), Some(25));
+    assert_eq!(kth_in_diff(&v0, &v3, 47), Some(29));
+    assert_eq!(kth_in_diff(&v0, &v3, 48), None);
+    //from version 2 the counts of 20..25 grow by 2, the others are unchanged
+    let n = seg.update(12, 15, &2);
+    assert_eq!(kth_in_diff(&v2, &seg.version(n), 0), Some(20));
+    assert_eq!(kth_in_diff(&v2, &seg.version(n), 9), Some(24));
+    assert_eq!(kth_in_diff(&v2, &seg.version(n), 10), None);
+}
+
+#[test]
+fn test_seg_persistent_extreme_values() {
+    let values = vec![i64::MAX - 1, i64::MAX - 7, i64::MAX - 1, i64::MAX - 3];
+    let t = KthInRange::new(&values);
+    assert_eq!(t.kth_in_range(0, 4, 0), Some(i64::MAX - 7));
+    assert_eq!(t.kth_in_range(0, 4, 2), Some(i64::MAX - 1));
+    assert_eq!(t.kth_in_range(1, 4, 1), Some(i64::MAX - 3));
+
+    let values = vec![i64::MIN, -(1 << 61), i64::MIN + 5, -2, i64::MIN];
+    let t = KthInRange::new(&values);
+    assert_eq!(t.kth_in_range(0, 5, 1), Some(i64::MIN));
+    assert_eq!(t.kth_in_range(0, 5, 2), Some(i64::MIN + 5));
+    assert_eq!(t.kth_in_range(1, 4, 2), Some(-2));
+
+    let lo = -(1 << 62) + 1;
+    let values = vec![(1 << 62) - 2, lo, 17];
+    let t = KthInRange::new(&values);
+    assert_eq!(t.kth_in_range(0, 3, 0), Some(lo));
+    assert_eq!(t.kth_in_range(0, 3, 1), Some(17));
+    assert_eq!(t.kth_in_range(0, 3, 2), Some((1 << 62) - 2));
+}
+
+#[test]
+#[should_panic]
+fn test_seg_persistent_max_value() {
+    KthInRange::new(&[3, i64::MAX]);
+}
@@ -3,128 +3,690 @@
 //! Fast Set Operations Using Treaps: http://www.cs.cmu.edu/afs/cs.cmu.edu/project/scandal/public/papers/treaps-spaa98.html
 
 use std::cell::RefCell;
-#[cfg(test)]
-use std::cmp::Ordering;
+use std::cmp::{max, min, Ordering};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::f32;
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::iter::Peekable;
+use std::ops::{Add, Bound, Mul, RangeBounds};
 use std::rc::{Rc, Weak};
 
 extern crate rand;
-use self::rand::Rng;
+use self::rand::distributions::uniform::SampleUniform;
+use self::rand::rngs::StdRng;
+use self::rand::{Rng, SeedableRng};
 
 #[cfg(test)]
 extern crate chrono;
 #[cfg(test)]
 use self::chrono::prelude::*;
 
-#[derive(Default, Clone, Debug)]
-pub struct Node<K, T>
+/// key and value are absent for the sentinel node of an empty tree
+#[derive(Clone, Debug)]
+pub struct Node<K, T, M = NoAgg>
 where
-    T: Clone + Default + Debug,
-    K: PartialOrd + Clone + Copy + Default + Debug,
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
 {
-    pub key: K,
+    pub key: Option<K>,
     pub prio: f32,
-    pub val: T,
-    pub parent: NodePtrWk<K, T>,
-    pub children: (Option<NodePtr<K, T>>, Option<NodePtr<K, T>>),
-    pub invalid: bool,
+    pub val: Option<T>,
+    pub parent: NodePtrWk<K, T, M>,
+    pub children: (Option<NodePtr<K, T, M>>, Option<NodePtr<K, T, M>>),
+    /// aggregate of the values in the subtree, the pending tag is included
+    pub agg: Option<M::Agg>,
+    /// tag pending to be applied to the children
+    pub tag: Option<M::Tag>,
 }
 
-#[derive(Default, Clone, Debug)]
-pub struct NodePtr<K, T>(pub Rc<RefCell<Node<K, T>>>)
+#[derive(Clone, Debug)]
+pub struct NodePtr<K, T, M = NoAgg>(pub Rc<RefCell<Node<K, T, M>>>)
 where
-    T: Clone + Default + Debug,
-    K: PartialOrd + Clone + Copy + Default + Debug;
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>;
 
-impl<K, T> From<Node<K, T>> for NodePtr<K, T>
+impl<K, T, M> From<Node<K, T, M>> for NodePtr<K, T, M>
 where
-    T: Clone + Default + Debug,
-    K: PartialOrd + Clone + Copy + Default + Debug,
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
 {
-    fn from(n: Node<K, T>) -> Self {
+    fn from(n: Node<K, T, M>) -> Self {
         Self(Rc::new(RefCell::new(n)))
     }
 }
 
-#[derive(Default, Clone, Debug)]
-pub struct NodePtrWk<K, T>(pub Weak<RefCell<Node<K, T>>>)
+impl<K, T, M> Default for NodePtr<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
+{
+    fn default() -> Self {
+        Self::with_aggregate()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NodePtrWk<K, T, M = NoAgg>(pub Weak<RefCell<Node<K, T, M>>>)
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>;
+
+impl<K, T, M> Default for NodePtrWk<K, T, M>
 where
-    T: Clone + Default + Debug,
-    K: PartialOrd + Clone + Copy + Default + Debug;
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
+{
+    fn default() -> Self {
+        Self(Weak::new())
+    }
+}
 
-impl<K, T> From<&NodePtr<K, T>> for NodePtrWk<K, T>
+impl<K, T, M> From<&NodePtr<K, T, M>> for NodePtrWk<K, T, M>
 where
-    T: Clone + Default + Debug,
-    K: PartialOrd + Clone + Copy + Default + Debug,
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
 {
-    fn from(n: &NodePtr<K, T>) -> Self {
+    fn from(n: &NodePtr<K, T, M>) -> Self {
         Self(Rc::downgrade(&n.0))
     }
 }
 
-pub enum SearchResult<K, T>
+/// f32 key with a total order (see f32::total_cmp), NaN is ordered above infinity
+#[derive(Default, Clone, Copy, Debug)]
+pub struct OrdF32(pub f32);
+
+impl PartialEq for OrdF32 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrdF32 {}
+
+impl PartialOrd for OrdF32 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrdF32 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for OrdF32 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl From<f32> for OrdF32 {
+    fn from(x: f32) -> Self {
+        Self(x)
+    }
+}
+
+/// f64 key with a total order (see f64::total_cmp), NaN is ordered above infinity
+#[derive(Default, Clone, Copy, Debug)]
+pub struct OrdF64(pub f64);
+
+impl PartialEq for OrdF64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrdF64 {}
+
+impl PartialOrd for OrdF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrdF64 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for OrdF64 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl From<f64> for OrdF64 {
+    fn from(x: f64) -> Self {
+        Self(x)
+    }
+}
+
+pub enum SearchResult<K, T, M = NoAgg>
 where
-    T: Clone + Default + Debug,
-    K: PartialOrd + Clone + Copy + Default + Debug,
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
 {
-    Exact(NodePtr<K, T>),
-    Nearest(NodePtr<K, T>),
+    Exact(NodePtr<K, T, M>),
+    Nearest(NodePtr<K, T, M>),
     Empty,
 }
 
+/// source of node priorities used on insertion
+pub trait Priority<K> {
+    fn priority(&mut self, k: &K) -> f32;
+}
+
+/// priorities from the thread local random number generator
+#[derive(Default, Clone, Debug)]
+pub struct PriorityRandom;
+
+impl<K> Priority<K> for PriorityRandom {
+    fn priority(&mut self, _k: &K) -> f32 {
+        let mut rng = rand::thread_rng();
+        rng.gen_range(-1e30_f32, 1e30_f32)
+    }
+}
+
+/// reproducible priorities from a seeded random number generator
+#[derive(Clone, Debug)]
+pub struct PrioritySeeded(StdRng);
+
+impl PrioritySeeded {
+    pub fn new(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<K> Priority<K> for PrioritySeeded {
+    fn priority(&mut self, _k: &K) -> f32 {
+        self.0.gen_range(-1e30_f32, 1e30_f32)
+    }
+}
+
+/// priorities derived from the hash of the key,
+/// the tree shape depends only on the set of keys as equal priorities are ordered by key;
+/// DefaultHasher output is not guaranteed to be stable across Rust releases,
+/// so shapes are only reproducible with the same toolchain
+#[derive(Default, Clone, Debug)]
+pub struct PriorityHash;
+
+impl<K> Priority<K> for PriorityHash
+where
+    K: Hash,
+{
+    fn priority(&mut self, k: &K) -> f32 {
+        let mut h = DefaultHasher::new();
+        k.hash(&mut h);
+        //non-negative finite f32 bit patterns order like the integers they encode,
+        //which gives 2^31 - 2^23 distinct priorities
+        f32::from_bits((h.finish() % 0x7f80_0000) as u32)
+    }
+}
+
+/// priority update of a node on a search hit, used by self-adjusting treaps
+/// where frequently accessed keys move towards the root
+pub trait PriorityAccess {
+    /// returns the new priority of a node with priority prio that is accessed
+    fn access(&mut self, prio: f32) -> f32;
+}
+
+/// low bits of the priority of PriorityAccessCount holding the random tie breaker
+const ACCESS_TIE_BITS: u32 = 10;
+
+/// bit pattern of f32::MAX
+const F32_MAX_BITS: u32 = 0x7f7f_ffff;
+
+///helper function: draws from the seeded generator if present, otherwise from the thread local one
+fn gen_range_opt<X>(rng: &mut Option<StdRng>, low: X, high: X) -> X
+where
+    X: SampleUniform,
+{
+    match rng {
+        Some(ref mut x) => x.gen_range(low, high),
+        None => rand::thread_rng().gen_range(low, high),
+    }
+}
+
+/// count based self-adjustment: the bits of the negated priority hold an integer access count
+/// above a random tie breaker given on insertion, so counts are exact and more accesses rank higher;
+/// the count saturates after 2^21 accesses of a node
+#[derive(Default, Clone, Debug)]
+pub struct PriorityAccessCount(Option<StdRng>);
+
+impl PriorityAccessCount {
+    /// reproducible tie breakers from a seeded random number generator
+    pub fn seeded(seed: u64) -> Self {
+        Self(Some(StdRng::seed_from_u64(seed)))
+    }
+
+    /// returns the access count held in prio
+    pub fn count(prio: f32) -> u32 {
+        Self::bits(prio) >> ACCESS_TIE_BITS
+    }
+
+    ///helper function
+    fn bits(prio: f32) -> u32 {
+        if prio < 0. {
+            (-prio).to_bits().min(F32_MAX_BITS)
+        } else {
+            0
+        }
+    }
+}
+
+impl<K> Priority<K> for PriorityAccessCount {
+    fn priority(&mut self, _k: &K) -> f32 {
+        -f32::from_bits(gen_range_opt(&mut self.0, 0, 1 << ACCESS_TIE_BITS))
+    }
+}
+
+impl PriorityAccess for PriorityAccessCount {
+    fn access(&mut self, prio: f32) -> f32 {
+        let bits = Self::bits(prio) + (1 << ACCESS_TIE_BITS);
+        if bits > F32_MAX_BITS {
+            prio
+        } else {
+            -f32::from_bits(bits)
+        }
+    }
+}
+
+/// randomized self-adjustment: a new random priority is drawn on access
+/// and kept if it ranks higher than the current one
+#[derive(Default, Clone, Debug)]
+pub struct PriorityAccessRandom(Option<StdRng>);
+
+impl PriorityAccessRandom {
+    /// reproducible priorities from a seeded random number generator
+    pub fn seeded(seed: u64) -> Self {
+        Self(Some(StdRng::seed_from_u64(seed)))
+    }
+}
+
+impl<K> Priority<K> for PriorityAccessRandom {
+    fn priority(&mut self, _k: &K) -> f32 {
+        gen_range_opt(&mut self.0, -1e30_f32, 1e30_f32)
+    }
+}
+
+impl PriorityAccess for PriorityAccessRandom {
+    fn access(&mut self, prio: f32) -> f32 {
+        prio.min(gen_range_opt(&mut self.0, -1e30_f32, 1e30_f32))
+    }
+}
+
+/// user supplied priority update on access
+impl<F> PriorityAccess for F
+where
+    F: FnMut(f32) -> f32,
+{
+    fn access(&mut self, prio: f32) -> f32 {
+        self(prio)
+    }
+}
+
+/// user supplied priority function
+impl<K, F> Priority<K> for F
+where
+    F: FnMut(&K) -> f32,
+{
+    fn priority(&mut self, k: &K) -> f32 {
+        self(k)
+    }
+}
+
+/// subtree aggregate maintained on every node of a treap,
+/// together with a tag that is lazily applied to all values of a subtree
+pub trait Aggregate<T>: Clone + Debug {
+    type Agg: Clone + Debug;
+    type Tag: Clone + Debug;
+
+    /// set when no aggregate is kept so that maintenance is skipped
+    const IS_NOOP: bool = false;
+
+    fn lift(val: &T) -> Self::Agg;
+
+    /// combines aggregates of adjacent key ranges in key order
+    fn combine(a: &Self::Agg, b: &Self::Agg) -> Self::Agg;
+
+    fn apply_val(tag: &Self::Tag, val: &T) -> T;
+
+    fn apply_agg(tag: &Self::Tag, agg: &Self::Agg) -> Self::Agg;
+
+    /// returns the tag equivalent to applying a followed by b
+    fn compose(a: &Self::Tag, b: &Self::Tag) -> Self::Tag;
+}
+
+/// default for treaps without aggregates
+#[derive(Default, Clone, Copy, Debug)]
+pub struct NoAgg;
+
+impl<T> Aggregate<T> for NoAgg
+where
+    T: Clone,
+{
+    type Agg = ();
+    type Tag = ();
+
+    const IS_NOOP: bool = true;
+
+    fn lift(_val: &T) {}
+
+    fn combine(_a: &(), _b: &()) {}
+
+    fn apply_val(_tag: &(), val: &T) -> T {
+        val.clone()
+    }
+
+    fn apply_agg(_tag: &(), _agg: &()) {}
+
+    fn compose(_a: &(), _b: &()) {}
+}
+
+/// (sum, count) of values, tag adds to all values
+#[derive(Default, Clone, Copy, Debug)]
+pub struct AggSum;
+
+impl<T> Aggregate<T> for AggSum
+where
+    T: Add<Output = T> + Mul<i64, Output = T> + Clone + Debug,
+{
+    type Agg = (T, i64);
+    type Tag = T;
+
+    fn lift(val: &T) -> (T, i64) {
+        (val.clone(), 1)
+    }
+
+    fn combine(a: &(T, i64), b: &(T, i64)) -> (T, i64) {
+        (a.0.clone() + b.0.clone(), a.1 + b.1)
+    }
+
+    fn apply_val(tag: &T, val: &T) -> T {
+        val.clone() + tag.clone()
+    }
+
+    fn apply_agg(tag: &T, agg: &(T, i64)) -> (T, i64) {
+        (agg.0.clone() + tag.clone() * agg.1, agg.1)
+    }
+
+    fn compose(a: &T, b: &T) -> T {
+        a.clone() + b.clone()
+    }
+}
+
+/// min of values, tag adds to all values
+#[derive(Default, Clone, Copy, Debug)]
+pub struct AggMin;
+
+impl<T> Aggregate<T> for AggMin
+where
+    T: Add<Output = T> + Ord + Clone + Debug,
+{
+    type Agg = T;
+    type Tag = T;
+
+    fn lift(val: &T) -> T {
+        val.clone()
+    }
+
+    fn combine(a: &T, b: &T) -> T {
+        min(a, b).clone()
+    }
+
+    fn apply_val(tag: &T, val: &T) -> T {
+        val.clone() + tag.clone()
+    }
+
+    fn apply_agg(tag: &T, agg: &T) -> T {
+        agg.clone() + tag.clone()
+    }
+
+    fn compose(a: &T, b: &T) -> T {
+        a.clone() + b.clone()
+    }
+}
+
+/// max of values, tag adds to all values
+#[derive(Default, Clone, Copy, Debug)]
+pub struct AggMax;
+
+impl<T> Aggregate<T> for AggMax
+where
+    T: Add<Output = T> + Ord + Clone + Debug,
+{
+    type Agg = T;
+    type Tag = T;
+
+    fn lift(val: &T) -> T {
+        val.clone()
+    }
+
+    fn combine(a: &T, b: &T) -> T {
+        max(a, b).clone()
+    }
+
+    fn apply_val(tag: &T, val: &T) -> T {
+        val.clone() + tag.clone()
+    }
+
+    fn apply_agg(tag: &T, agg: &T) -> T {
+        agg.clone() + tag.clone()
+    }
+
+    fn compose(a: &T, b: &T) -> T {
+        a.clone() + b.clone()
+    }
+}
+
 pub enum ChildBranch {
     Left,
     Right,
     NotApplicable,
 }
 
-impl<K, T> NodePtr<K, T>
+/// lazy double-ended in-order iterator over nodes of a treap
+struct Nodes<K, T, M>
 where
-    T: Clone + Default + Debug,
-    K: PartialOrd + Clone + Copy + Default + Debug,
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
 {
-    ///helper function
-    fn gen_priority_random() -> f32 {
-        let mut rng = rand::thread_rng();
-        let r = rng.gen_range(-1e30_f32, 1e30_f32);
-        r
+    front: Option<NodePtr<K, T, M>>,
+    back: Option<NodePtr<K, T, M>>,
+}
+
+impl<K, T, M> Iterator for Nodes<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
+{
+    type Item = NodePtr<K, T, M>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cur = self.front.take()?;
+        match self.back {
+            Some(ref b) if Rc::ptr_eq(&b.0, &cur.0) => {
+                self.back = None;
+            }
+            _ => {
+                self.front = cur.successor();
+            }
+        }
+        Some(cur)
+    }
+}
+
+impl<K, T, M> DoubleEndedIterator for Nodes<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let cur = self.back.take()?;
+        match self.front {
+            Some(ref f) if Rc::ptr_eq(&f.0, &cur.0) => {
+                self.front = None;
+            }
+            _ => {
+                self.back = cur.predecessor();
+            }
+        }
+        Some(cur)
+    }
+}
+
+/// lazy double-ended in-order iterator over (key, value) pairs of a treap
+pub struct Iter<K, T, M = NoAgg>(Nodes<K, T, M>)
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>;
+
+impl<K, T, M> Iterator for Iter<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
+{
+    type Item = (K, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|x| (x.key(), x.val()))
+    }
+}
+
+impl<K, T, M> DoubleEndedIterator for Iter<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|x| (x.key(), x.val()))
+    }
+}
+
+/// change of a key between 2 versions of a treap
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diff<K, T> {
+    Added(K, T),
+    Removed(K, T),
+    /// (key, old value, new value)
+    Changed(K, T, T),
+}
+
+/// lazy iterator over the changes between 2 treaps in key order
+pub struct DiffIter<K, T, M = NoAgg>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
+{
+    a: Peekable<Iter<K, T, M>>,
+    b: Peekable<Iter<K, T, M>>,
+}
+
+impl<K, T, M> Iterator for DiffIter<K, T, M>
+where
+    T: Clone + Debug + PartialEq,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
+{
+    type Item = Diff<K, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ord = match (self.a.peek(), self.b.peek()) {
+                (Some(x), Some(y)) => x.0.cmp(&y.0),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => return None,
+            };
+            match ord {
+                Ordering::Less => {
+                    let (k, v) = self.a.next().unwrap();
+                    return Some(Diff::Removed(k, v));
+                }
+                Ordering::Greater => {
+                    let (k, v) = self.b.next().unwrap();
+                    return Some(Diff::Added(k, v));
+                }
+                Ordering::Equal => {
+                    let (k, v_old) = self.a.next().unwrap();
+                    let (_, v_new) = self.b.next().unwrap();
+                    if v_old != v_new {
+                        return Some(Diff::Changed(k, v_old, v_new));
+                    }
+                }
+            }
+        }
     }
+}
 
-    pub fn child_l(&self) -> Option<NodePtr<K, T>> {
+impl<K, T, M> NodePtr<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Aggregate<T>,
+{
+    pub fn child_l(&self) -> Option<NodePtr<K, T, M>> {
+        self.push_down();
         self.0.borrow().children.0.clone()
     }
 
-    pub fn child_r(&self) -> Option<NodePtr<K, T>> {
+    pub fn child_r(&self) -> Option<NodePtr<K, T, M>> {
+        self.push_down();
         self.0.borrow().children.1.clone()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.borrow().invalid
+        self.0.borrow().key.is_none()
     }
 
     pub fn is_leaf(&self) -> bool {
         self.child_l().is_none() && self.child_r().is_none()
     }
 
-    pub fn par(&self) -> NodePtrWk<K, T> {
+    pub fn par(&self) -> NodePtrWk<K, T, M> {
         self.0.borrow().parent.clone()
     }
 
     pub fn key(&self) -> K {
-        self.0.borrow().key.clone()
+        self.0.borrow().key.clone().expect("key of empty node")
     }
 
     pub fn val(&self) -> T {
-        self.0.borrow().val.clone()
+        self.push_path();
+        self.0.borrow().val.clone().expect("value of empty node")
     }
 
     pub fn prio(&self) -> f32 {
         self.0.borrow().prio
     }
 
-    fn link_left(&self, child: &Option<NodePtr<K, T>>) {
+    /// heap order of nodes: lower prio is closer to the root and ties are broken by key,
+    /// so the tree shape only depends on the (key, priority) pairs
+    pub fn ranks_above(&self, other: &Self) -> bool {
+        let (a, b) = (self.0.borrow(), other.0.borrow());
+        a.prio < b.prio || (a.prio == b.prio && a.key < b.key)
+    }
+
+    fn link_left(&self, child: &Option<NodePtr<K, T, M>>) {
         match child {
             Some(x) => {
                 x.0.borrow_mut().parent = NodePtrWk::from(self);
@@ -136,7 +698,7 @@ where
         }
     }
 
-    fn link_right(&self, child: &Option<NodePtr<K, T>>) {
+    fn link_right(&self, child: &Option<NodePtr<K, T, M>>) {
         match child {
             Some(x) => {
                 x.0.borrow_mut().parent = NodePtrWk::from(self);
@@ -149,7 +711,7 @@ where
     }
 
     ///helper function
-    fn child_branch(&self, child: &Option<NodePtr<K, T>>) -> ChildBranch {
+    fn child_branch(&self, child: &Option<NodePtr<K, T, M>>) -> ChildBranch {
         match child {
             Some(x) => {
                 match (
@@ -180,34 +742,199 @@ where
         ChildBranch::NotApplicable
     }
 
-    pub fn new() -> Self {
-        let mut n = Node::default();
-        n.invalid = true;
-        NodePtr::from(n)
+    /// returns an empty tree maintaining subtree aggregates of M
+    pub fn with_aggregate() -> Self {
+        NodePtr::from(Node {
+            key: None,
+            prio: 0.,
+            val: None,
+            parent: Default::default(),
+            children: (None, None),
+            agg: None,
+            tag: None,
+        })
     }
 
-    pub fn search(&self, k: K) -> SearchResult<K, T> {
-        if self.0.borrow().invalid {
-            SearchResult::Empty
-        } else if k < (*self.0).borrow().key {
-            match (*self.0).borrow_mut().children.0 {
-                Some(ref mut x) => x.search(k),
-                _ => SearchResult::Nearest(self.clone()),
-            }
-        } else if k > (*self.0).borrow().key {
-            match (*self.0).borrow_mut().children.1 {
-                Some(ref mut x) => x.search(k),
-                _ => SearchResult::Nearest(self.clone()),
-            }
-        } else {
-            SearchResult::Exact(self.clone())
+    ///helper function
+    fn combine_opt(a: Option<M::Agg>, b: Option<M::Agg>) -> Option<M::Agg> {
+        match (a, b) {
+            (Some(x), Some(y)) => Some(M::combine(&x, &y)),
+            (x, None) => x,
+            (None, y) => y,
         }
     }
 
-    pub fn get_root(&self) -> NodePtr<K, T> {
-        let mut n = self.clone();
+    ///helper function: applies tag to the value and aggregate of current node
+    ///and keeps it pending for the children
+    fn apply_tag(&self, tag: &M::Tag) {
+        let mut n = self.0.borrow_mut();
+        if n.key.is_none() {
+            return;
+        }
+        n.val = n.val.as_ref().map(|v| M::apply_val(tag, v));
+        n.agg = n.agg.as_ref().map(|a| M::apply_agg(tag, a));
+        n.tag = Some(match n.tag {
+            Some(ref t) => M::compose(t, tag),
+            None => tag.clone(),
+        });
+    }
 
-        loop {
+    ///helper function: moves pending tag of current node to its children
+    fn push_down(&self) {
+        if M::IS_NOOP {
+            return;
+        }
+        let tag = self.0.borrow_mut().tag.take();
+        if let Some(t) = tag {
+            let (l, r) = self.0.borrow().children.clone();
+            for x in l.iter().chain(r.iter()) {
+                x.apply_tag(&t);
+            }
+        }
+    }
+
+    ///helper function: moves pending tags of ancestors down to current node
+    fn push_path(&self) {
+        if M::IS_NOOP {
+            return;
+        }
+        let mut path = vec![];
+        let mut cur = self.par().0.upgrade();
+        while let Some(x) = cur {
+            let x = NodePtr(x);
+            cur = x.par().0.upgrade();
+            path.push(x);
+        }
+        for x in path.iter().rev() {
+            x.push_down();
+        }
+    }
+
+    ///helper function: recomputes aggregate of current node from its children
+    fn pull(&self) {
+        if M::IS_NOOP {
+            return;
+        }
+        let agg = {
+            let n = self.0.borrow();
+            debug_assert!(n.tag.is_none());
+            let l = n.children.0.as_ref().and_then(|x| x.0.borrow().agg.clone());
+            let r = n.children.1.as_ref().and_then(|x| x.0.borrow().agg.clone());
+            let a = Self::combine_opt(l, n.val.as_ref().map(M::lift));
+            Self::combine_opt(a, r)
+        };
+        self.0.borrow_mut().agg = agg;
+    }
+
+    ///helper function: recomputes aggregates from current node up to the root
+    fn pull_path(&self) {
+        if M::IS_NOOP {
+            return;
+        }
+        let mut cur = Some(self.clone());
+        while let Some(x) = cur {
+            x.pull();
+            cur = x.par().0.upgrade().map(NodePtr);
+        }
+    }
+
+    /// returns aggregate of all values in the subtree
+    pub fn aggregate(&self) -> Option<M::Agg> {
+        self.push_path();
+        self.0.borrow().agg.clone()
+    }
+
+    /// returns aggregate of values in the subtree with keys in [k_l, k_r) in O(depth)
+    pub fn aggregate_range(&self, k_l: K, k_r: K) -> Option<M::Agg> {
+        if self.is_empty() {
+            return None;
+        }
+        self.push_path();
+        self.aggregate_range_impl(&k_l, &k_r, false, false)
+    }
+
+    ///helper function: ge_l and lt_r are set if all keys of the subtree are >= k_l and < k_r respectively
+    fn aggregate_range_impl(&self, k_l: &K, k_r: &K, ge_l: bool, lt_r: bool) -> Option<M::Agg> {
+        if ge_l && lt_r {
+            return self.0.borrow().agg.clone();
+        }
+        let k = self.key();
+        let mut ret = None;
+        if *k_l < k {
+            if let Some(x) = self.child_l() {
+                ret = x.aggregate_range_impl(k_l, k_r, ge_l, lt_r || k <= *k_r);
+            }
+        }
+        if *k_l <= k && k < *k_r {
+            let v = self.0.borrow().val.as_ref().map(M::lift);
+            ret = Self::combine_opt(ret, v);
+        }
+        if k < *k_r {
+            if let Some(x) = self.child_r() {
+                let r = x.aggregate_range_impl(k_l, k_r, ge_l || *k_l <= k, lt_r);
+                ret = Self::combine_opt(ret, r);
+            }
+        }
+        ret
+    }
+
+    /// applies tag to values in the subtree with keys in [k_l, k_r) in O(depth),
+    /// does nothing for treaps without an aggregate
+    pub fn apply_range(&self, k_l: K, k_r: K, tag: M::Tag) {
+        if !self.is_empty() && !M::IS_NOOP {
+            self.push_path();
+            self.apply_range_impl(&k_l, &k_r, false, false, &tag);
+            if let Some(p) = self.par().0.upgrade() {
+                NodePtr(p).pull_path();
+            }
+        }
+    }
+
+    ///helper function: ge_l and lt_r are set if all keys of the subtree are >= k_l and < k_r respectively
+    fn apply_range_impl(&self, k_l: &K, k_r: &K, ge_l: bool, lt_r: bool, tag: &M::Tag) {
+        if ge_l && lt_r {
+            self.apply_tag(tag);
+            return;
+        }
+        self.push_down();
+        let k = self.key();
+        if *k_l < k {
+            if let Some(x) = self.child_l() {
+                x.apply_range_impl(k_l, k_r, ge_l, lt_r || k <= *k_r, tag);
+            }
+        }
+        if *k_l <= k && k < *k_r {
+            let mut n = self.0.borrow_mut();
+            n.val = n.val.as_ref().map(|v| M::apply_val(tag, v));
+        }
+        if k < *k_r {
+            if let Some(x) = self.child_r() {
+                x.apply_range_impl(k_l, k_r, ge_l || *k_l <= k, lt_r, tag);
+            }
+        }
+        self.pull();
+    }
+
+    pub fn search(&self, k: K) -> SearchResult<K, T, M> {
+        let ord = match self.0.borrow().key {
+            Some(ref x) => k.cmp(x),
+            None => return SearchResult::Empty,
+        };
+        let next = match ord {
+            Ordering::Less => self.child_l(),
+            Ordering::Greater => self.child_r(),
+            Ordering::Equal => return SearchResult::Exact(self.clone()),
+        };
+        match next {
+            Some(x) => x.search(k),
+            _ => SearchResult::Nearest(self.clone()),
+        }
+    }
+
+    pub fn get_root(&self) -> NodePtr<K, T, M> {
+        let mut n = self.clone();
+
+        loop {
             let m = match n.0.borrow().parent.0.upgrade() {
                 Some(x) => x,
                 _ => {
@@ -222,12 +949,20 @@ where
 
     ///inserts a node and returns ( root, already exists )
     ///existing node value and priority is updated
-    pub fn insert_with_priority(&self, k: K, val: T, priority: f32) -> (NodePtr<K, T>, bool) {
-        match self.search(k) {
+    pub fn insert_with_priority(&self, k: K, val: T, priority: f32) -> (NodePtr<K, T, M>, bool) {
+        self.insert_node(k, Some(val), priority)
+    }
+
+    ///helper function: inserts a node and returns ( root, already exists )
+    ///value of an existing node is kept if val is not present
+    fn insert_node(&self, k: K, val: Option<T>, priority: f32) -> (NodePtr<K, T, M>, bool) {
+        match self.search(k.clone()) {
             SearchResult::Exact(x) => {
                 {
                     let mut n = x.0.borrow_mut();
-                    n.val = val;
+                    if val.is_some() {
+                        n.val = val;
+                    }
                     n.prio = priority;
                 }
 
@@ -239,38 +974,48 @@ where
                 //fix downward pass
                 let _root2 = x.fixdown_priority();
 
+                x.pull_path();
+
                 (self.get_root(), true)
             }
             SearchResult::Nearest(x) => {
+                let is_left = k < x.key();
+
                 let n = Node {
-                    key: k,
+                    key: Some(k),
                     prio: priority,
-                    val: val,
+                    val,
                     parent: Default::default(),
                     children: (None, None),
-                    invalid: false,
+                    agg: None,
+                    tag: None,
                 };
 
                 let child = Some(NodePtr::from(n));
 
-                if k < x.0.borrow().key {
+                if is_left {
                     x.link_left(&child);
                 } else {
                     x.link_right(&child);
                 }
 
-                let _root = child.as_ref().unwrap().fixup_priority();
+                let c = child.as_ref().unwrap();
+
+                let _root = c.fixup_priority();
+
+                c.pull_path();
 
                 (self.get_root(), false)
             }
             SearchResult::Empty => {
                 //create new node using current stale node
-                self.0.borrow_mut().invalid = false;
-                self.0.borrow_mut().key = k;
+                self.0.borrow_mut().key = Some(k);
                 self.0.borrow_mut().val = val;
                 self.0.borrow_mut().prio = priority;
                 self.0.borrow_mut().children = (None, None);
                 self.0.borrow_mut().parent = Default::default();
+                self.0.borrow_mut().tag = None;
+                self.pull();
 
                 (self.get_root(), false)
             }
@@ -278,11 +1023,93 @@ where
     }
 
     ///inserts a node and returns ( root, already_exists )
-    pub fn insert(&self, k: K, val: T) -> (NodePtr<K, T>, bool) {
-        let prio = Self::gen_priority_random();
+    pub fn insert(&self, k: K, val: T) -> (NodePtr<K, T, M>, bool) {
+        self.insert_with_priority_source(k, val, &mut PriorityRandom)
+    }
+
+    ///inserts a node with priority drawn from prio_src and returns ( root, already_exists )
+    pub fn insert_with_priority_source<P>(
+        &self,
+        k: K,
+        val: T,
+        prio_src: &mut P,
+    ) -> (NodePtr<K, T, M>, bool)
+    where
+        P: Priority<K>,
+    {
+        let prio = prio_src.priority(&k);
         self.insert_with_priority(k, val, prio)
     }
 
+    /// builds a treap maintaining aggregates of M in O(n) from items with strictly increasing keys
+    pub fn from_sorted_with_aggregate<I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = (K, T)>,
+    {
+        Self::from_sorted_with_aggregate_and_priority_source(items, &mut PriorityRandom)
+    }
+
+    /// builds a treap maintaining aggregates of M in O(n) from items with strictly increasing keys,
+    /// priorities are drawn from prio_src in order of the items
+    pub fn from_sorted_with_aggregate_and_priority_source<I, P>(items: I, prio_src: &mut P) -> Self
+    where
+        I: IntoIterator<Item = (K, T)>,
+        P: Priority<K>,
+    {
+        Self::from_sorted_with_aggregate_and_priority(items.into_iter().map(|(k, val)| {
+            let prio = prio_src.priority(&k);
+            (k, val, prio)
+        }))
+    }
+
+    /// builds a treap maintaining aggregates of M in O(n) from (key, value, priority) items
+    /// with strictly increasing keys by maintaining the right spine of the tree in a stack
+    pub fn from_sorted_with_aggregate_and_priority<I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = (K, T, f32)>,
+    {
+        let mut spine: Vec<Self> = vec![];
+        for (k, val, prio) in items {
+            if let Some(x) = spine.last() {
+                if let Some(ref y) = x.0.borrow().key {
+                    assert!(*y < k, "keys not strictly increasing");
+                }
+            }
+            let n = NodePtr::from(Node {
+                key: Some(k),
+                prio,
+                val: Some(val),
+                parent: Default::default(),
+                children: (None, None),
+                agg: None,
+                tag: None,
+            });
+            let mut last = None;
+            while let Some(x) = spine.pop() {
+                if x.prio() > prio {
+                    //subtree of a node leaving the spine is complete
+                    x.pull();
+                    last = Some(x);
+                } else {
+                    spine.push(x);
+                    break;
+                }
+            }
+            n.link_left(&last);
+            if let Some(x) = spine.last() {
+                x.link_right(&Some(n.clone()));
+            }
+            spine.push(n);
+        }
+        for x in spine.iter().rev() {
+            x.pull();
+        }
+        match spine.first() {
+            Some(x) => x.clone(),
+            _ => Self::with_aggregate(),
+        }
+    }
+
     pub fn link_grandparent(&self) {
         let p = match self.0.borrow().parent.0.upgrade() {
             Some(x) => NodePtr(x),
@@ -337,6 +1164,9 @@ where
             }
         };
 
+        p.push_down();
+        self.push_down();
+
         self.link_grandparent();
 
         let temp = self.0.borrow().children.1.clone();
@@ -345,8 +1175,12 @@ where
 
         p.link_left(&temp);
 
+        p.pull();
+
         self.link_right(&Some(p));
 
+        self.pull();
+
         self.clone()
     }
 
@@ -377,6 +1211,9 @@ where
             }
         };
 
+        p.push_down();
+        self.push_down();
+
         self.link_grandparent();
 
         let temp = self.0.borrow().children.0.clone();
@@ -385,8 +1222,12 @@ where
 
         p.link_right(&temp);
 
+        p.pull();
+
         self.link_left(&Some(p));
 
+        self.pull();
+
         self.clone()
     }
 
@@ -394,8 +1235,6 @@ where
     /// this moves a node with low priority upward
     /// return a node if it is the new root
     pub fn fixup_priority(&self) -> Option<Self> {
-        let prio = self.0.borrow().prio;
-
         let mut p = self.par().0.upgrade();
 
         loop {
@@ -403,7 +1242,7 @@ where
                 Some(ref x) => {
                     let parent = NodePtr(x.clone());
 
-                    if parent.0.borrow().prio < prio {
+                    if parent.ranks_above(self) {
                         break;
                     } else {
                         match parent.child_branch(&Some(self.clone())) {
@@ -443,17 +1282,13 @@ where
         loop {
             match (x.child_l().as_ref(), x.child_r().as_ref()) {
                 (Some(l), Some(r)) => {
-                    if l.0.borrow().prio < r.0.borrow().prio
-                        && l.0.borrow().prio < x.0.borrow().prio
-                    {
+                    if l.ranks_above(r) && l.ranks_above(&x) {
                         let n = l.rot_up_left();
                         match n.clone().0.borrow().parent.0.upgrade().as_ref() {
                             None => root = Some(n),
                             _ => {}
                         }
-                    } else if l.0.borrow().prio > r.0.borrow().prio
-                        && r.0.borrow().prio < x.0.borrow().prio
-                    {
+                    } else if r.ranks_above(l) && r.ranks_above(&x) {
                         let n = r.rot_up_right();
 
                         match n.clone().0.borrow().parent.0.upgrade().as_ref() {
@@ -465,7 +1300,7 @@ where
                     }
                 }
                 (Some(l), None) => {
-                    if l.0.borrow().prio < x.0.borrow().prio {
+                    if l.ranks_above(&x) {
                         let n = l.rot_up_left();
 
                         match n.clone().0.borrow().parent.0.upgrade().as_ref() {
@@ -477,7 +1312,7 @@ where
                     }
                 }
                 (None, Some(r)) => {
-                    if r.0.borrow().prio < x.0.borrow().prio {
+                    if r.ranks_above(&x) {
                         let n = r.rot_up_right();
 
                         match n.clone().0.borrow().parent.0.upgrade().as_ref() {
@@ -498,6 +1333,8 @@ where
 
     /// removes current node and returns the root
     pub fn remove(&self) -> Self {
+        self.push_path();
+
         let mut n = self.get_root();
 
         {
@@ -513,8 +1350,11 @@ where
         }
 
         if Rc::ptr_eq(&self.0, &n.0) {
-            //leave node as sentil for empty tree by marking the invalid bit
-            self.0.borrow_mut().invalid = true;
+            //leave node as sentil for empty tree by clearing key and value
+            self.0.borrow_mut().key = None;
+            self.0.borrow_mut().val = None;
+            self.0.borrow_mut().agg = None;
+            self.0.borrow_mut().tag = None;
         } else {
             //remove node
 
@@ -535,6 +1375,8 @@ where
 
             //there should be 1 less strong ref count for the current node
             debug_assert_eq!(1, ref_count - Rc::strong_count(&self.0));
+
+            p.pull_path();
         }
 
         n
@@ -617,61 +1459,374 @@ where
         }
     }
 
+    /// returns the node with the lowest key in the subtree
+    pub fn first(&self) -> Option<Self> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut cur = self.clone();
+        while let Some(x) = cur.child_l() {
+            cur = x;
+        }
+        Some(cur)
+    }
+
+    /// returns the node with the highest key in the subtree
+    pub fn last(&self) -> Option<Self> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut cur = self.clone();
+        while let Some(x) = cur.child_r() {
+            cur = x;
+        }
+        Some(cur)
+    }
+
+    ///helper function: returns the node with the lowest key within the lower bound
+    fn bound_lower(&self, b: Bound<&K>) -> Option<Self> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut ret = None;
+        let mut cur = Some(self.clone());
+        while let Some(x) = cur {
+            let within = match b {
+                Bound::Included(k) => *k <= x.key(),
+                Bound::Excluded(k) => *k < x.key(),
+                Bound::Unbounded => true,
+            };
+            if within {
+                cur = x.child_l();
+                ret = Some(x);
+            } else {
+                cur = x.child_r();
+            }
+        }
+        ret
+    }
+
+    ///helper function: returns the node with the highest key within the upper bound
+    fn bound_upper(&self, b: Bound<&K>) -> Option<Self> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut ret = None;
+        let mut cur = Some(self.clone());
+        while let Some(x) = cur {
+            let within = match b {
+                Bound::Included(k) => x.key() <= *k,
+                Bound::Excluded(k) => x.key() < *k,
+                Bound::Unbounded => true,
+            };
+            if within {
+                cur = x.child_r();
+                ret = Some(x);
+            } else {
+                cur = x.child_l();
+            }
+        }
+        ret
+    }
+
+    /// returns a lazy in-order iterator over the (key, value) pairs of the subtree
+    pub fn iter(&self) -> Iter<K, T, M> {
+        self.range(..)
+    }
+
+    /// returns a lazy in-order iterator over the (key, value) pairs of the subtree with keys in range
+    pub fn range<R>(&self, range: R) -> Iter<K, T, M>
+    where
+        R: RangeBounds<K>,
+    {
+        Iter(self.range_nodes(range))
+    }
+
+    ///helper function
+    fn range_nodes<R>(&self, range: R) -> Nodes<K, T, M>
+    where
+        R: RangeBounds<K>,
+    {
+        let front = self.bound_lower(range.start_bound());
+        let back = self.bound_upper(range.end_bound());
+        match (front, back) {
+            (Some(a), Some(b)) if a.key() <= b.key() => Nodes {
+                front: Some(a),
+                back: Some(b),
+            },
+            _ => Nodes {
+                front: None,
+                back: None,
+            },
+        }
+    }
+
     /// return [x| x.key >= k_l && k.key < k_r ]
     pub fn query_key_range(&self, k_l: K, k_r: K) -> Vec<Self> {
-        let start = match self.search(k_l) {
-            SearchResult::Exact(x) => {
-                if x.key() < k_r {
-                    Some(x)
-                } else {
-                    None
-                }
-            }
-            SearchResult::Nearest(x) => {
-                if x.key() < k_l {
-                    let mut cur = x;
-                    while let Some(y) = cur.successor() {
-                        cur = y;
-                        if !(cur.key() < k_l) {
-                            break;
-                        }
-                    }
-                    if !(cur.key() < k_l) && cur.key() < k_r {
-                        Some(cur)
-                    } else {
-                        None
+        self.range_nodes(k_l..k_r).collect()
+    }
+
+    /// returns a lazy iterator over the changes from self to other in key order in O(n + m)
+    pub fn diff(&self, other: &Self) -> DiffIter<K, T, M>
+    where
+        T: PartialEq,
+    {
+        DiffIter {
+            a: self.get_root().iter().peekable(),
+            b: other.get_root().iter().peekable(),
+        }
+    }
+
+    /// applies changes to the tree and returns the root,
+    /// changes are taken as a Vec so a lazy diff of the same tree is collected before it is applied
+    pub fn apply(&self, diff: Vec<Diff<K, T>>) -> Self {
+        let mut root = self.get_root();
+        for d in diff {
+            root = match d {
+                Diff::Added(k, v) => root.insert(k, v).0,
+                Diff::Removed(k, _) => root.remove_by_key(k),
+                Diff::Changed(k, _, v) => match root.search(k.clone()) {
+                    SearchResult::Exact(x) => {
+                        x.push_path();
+                        x.0.borrow_mut().val = Some(v);
+                        x.pull_path();
+                        root
                     }
-                } else if x.key() < k_r {
-                    Some(x)
-                } else {
-                    None
+                    _ => root.insert(k, v).0,
+                },
+            };
+        }
+        root
+    }
+
+    ///helper function: climbs from current node to the lowest ancestor whose subtree
+    ///key range contains k, or to the root
+    fn climb(&self, k: &K) -> Self {
+        let mut cur = self.clone();
+        let (mut lo_ok, mut hi_ok) = (false, false);
+        while let Some(y) = cur.par().0.upgrade() {
+            let p = NodePtr(y);
+            let pk = p.key();
+            match p.child_branch(&Some(cur.clone())) {
+                ChildBranch::Left => hi_ok = hi_ok || *k < pk,
+                ChildBranch::Right => lo_ok = lo_ok || pk < *k,
+                _ => {
+                    panic!("parent child link non-existent");
                 }
             }
+            if lo_ok && hi_ok {
+                break;
+            }
+            cur = p;
+        }
+        cur
+    }
+
+    /// searches for k starting from the current node as a finger:
+    /// climbs via parent links until k is within the subtree and then descends,
+    /// O(log d) expected where d is the rank distance between k and the finger
+    pub fn search_from(&self, k: K) -> SearchResult<K, T, M> {
+        if self.is_empty() {
+            return SearchResult::Empty;
+        }
+        self.climb(&k).search(k)
+    }
+
+    ///helper function: lower bound search from current node as a finger
+    fn finger_lower(&self, b: Bound<&K>) -> Option<Self> {
+        let (k, incl) = match b {
+            Bound::Included(k) => (k, true),
+            Bound::Excluded(k) => (k, false),
+            Bound::Unbounded => return self.get_root().first(),
+        };
+        match self.search_from(k.clone()) {
+            SearchResult::Exact(x) if incl => Some(x),
+            SearchResult::Exact(x) => x.successor(),
+            SearchResult::Nearest(x) if x.key() > *k => Some(x),
+            SearchResult::Nearest(x) => x.successor(),
             SearchResult::Empty => None,
+        }
+    }
+
+    ///helper function: upper bound search from current node as a finger
+    fn finger_upper(&self, b: Bound<&K>) -> Option<Self> {
+        let (k, incl) = match b {
+            Bound::Included(k) => (k, true),
+            Bound::Excluded(k) => (k, false),
+            Bound::Unbounded => return self.get_root().last(),
         };
+        match self.search_from(k.clone()) {
+            SearchResult::Exact(x) if incl => Some(x),
+            SearchResult::Exact(x) => x.predecessor(),
+            SearchResult::Nearest(x) if x.key() < *k => Some(x),
+            SearchResult::Nearest(x) => x.predecessor(),
+            SearchResult::Empty => None,
+        }
+    }
 
-        if start.is_none() {
-            vec![]
-        } else {
-            let mut cur = start.unwrap();
-            let mut ret = vec![cur.clone()];
-            while let Some(n) = cur.successor() {
-                if n.key() < k_r {
-                    ret.push(n.clone());
-                    cur = n;
+    /// returns a lazy in-order iterator over the (key, value) pairs of the whole tree with keys in range,
+    /// both ends are located by finger search from the current node
+    pub fn range_from<R>(&self, range: R) -> Iter<K, T, M>
+    where
+        R: RangeBounds<K>,
+    {
+        Iter(self.range_from_nodes(range))
+    }
+
+    ///helper function
+    fn range_from_nodes<R>(&self, range: R) -> Nodes<K, T, M>
+    where
+        R: RangeBounds<K>,
+    {
+        let front = self.finger_lower(range.start_bound());
+        let back = match front {
+            Some(ref a) => a.finger_upper(range.end_bound()),
+            _ => None,
+        };
+        match (front, back) {
+            (Some(a), Some(b)) if a.key() <= b.key() => Nodes {
+                front: Some(a),
+                back: Some(b),
+            },
+            _ => Nodes {
+                front: None,
+                back: None,
+            },
+        }
+    }
+
+    /// return [x| x.key >= k_l && k.key < k_r ] of the whole tree using finger search from the current node
+    pub fn query_key_range_from(&self, k_l: K, k_r: K) -> Vec<Self> {
+        self.range_from_nodes(k_l..k_r).collect()
+    }
+
+    /// lower prio ranks higher as it is closer to the root;
+    /// returns the highest ranked node of [x| x.key >= k_l && k.key < k_r ] in O(depth)
+    pub fn query_top_priority(&self, k_l: K, k_r: K) -> Option<Self> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut cur = Some(self.clone());
+        while let Some(x) = cur {
+            let k = x.key();
+            if k < k_l {
+                cur = x.child_r();
+            } else if k >= k_r {
+                cur = x.child_l();
+            } else {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    /// lower prio ranks higher as it is closer to the root;
+    /// return [x| x.key >= k_l && k.key < k_r && x.prio < p ] in key order,
+    /// subtrees ranked at or below p are not visited
+    pub fn query_priority_above(&self, k_l: K, k_r: K, p: f32) -> Vec<Self> {
+        let mut ret = vec![];
+        if !self.is_empty() {
+            self.collect_priority_above(&k_l, &k_r, p, &mut ret);
+        }
+        ret
+    }
+
+    ///helper function
+    fn collect_priority_above(&self, k_l: &K, k_r: &K, p: f32, ret: &mut Vec<Self>) {
+        if self.prio() >= p {
+            return;
+        }
+        let (go_left, inside, go_right) = {
+            let n = self.0.borrow();
+            let k = n.key.as_ref().expect("key of empty node");
+            (k_l < k, k_l <= k && k < k_r, k < k_r)
+        };
+        if go_left {
+            if let Some(x) = self.child_l() {
+                x.collect_priority_above(k_l, k_r, p, ret);
+            }
+        }
+        if inside {
+            ret.push(self.clone());
+        }
+        if go_right {
+            if let Some(x) = self.child_r() {
+                x.collect_priority_above(k_l, k_r, p, ret);
+            }
+        }
+    }
+
+    /// updates priority of the current node in place and returns the root
+    pub fn set_priority(&self, priority: f32) -> Self {
+        if self.is_empty() {
+            return self.clone();
+        }
+        self.push_path();
+        self.0.borrow_mut().prio = priority;
+
+        //new root after rotations is only held by the returned handles
+        let _root = self.fixup_priority();
+        let _root2 = self.fixdown_priority();
+
+        self.pull_path();
+        self.get_root()
+    }
+
+    /// updates priority of the node with key k in place and returns the root
+    pub fn set_priority_by_key(&self, k: K, priority: f32) -> Self {
+        match self.search(k) {
+            SearchResult::Exact(x) => x.set_priority(priority),
+            _ => self.get_root(),
+        }
+    }
+
+    /// searches for k and raises priority of a found node according to access_src,
+    /// returns (root, search result)
+    pub fn search_adjust<A>(&self, k: K, access_src: &mut A) -> (Self, SearchResult<K, T, M>)
+    where
+        A: PriorityAccess,
+    {
+        match self.search(k) {
+            SearchResult::Exact(x) => {
+                let prio = access_src.access(x.prio());
+                let root = if prio < x.prio() {
+                    x.set_priority(prio)
                 } else {
-                    break;
-                }
+                    self.get_root()
+                };
+                (root, SearchResult::Exact(x))
             }
-            ret
+            r => (self.get_root(), r),
+        }
+    }
+
+    /// returns number of edges from the root to the current node
+    pub fn depth(&self) -> usize {
+        let mut d = 0;
+        let mut cur = self.par().0.upgrade();
+        while let Some(x) = cur {
+            d += 1;
+            cur = x.borrow().parent.0.upgrade();
         }
+        d
     }
+
     /// returns ((a, b), c) such that a: [x| x.key<k], b: [x| x.key>k]
     /// and c is present if c.key == k
     pub fn split_by_key(&self, k: K) -> ((Self, Self), Option<Self>) {
-        //insert node with a sentil lowest priority so it's at the root
+        //an empty root would take the key of the sentinel node
+        if self.is_empty() {
+            return ((Self::with_aggregate(), Self::with_aggregate()), None);
+        }
 
-        let (root, exists) = self.insert_with_priority(k, Default::default(), f32::NEG_INFINITY);
+        //keep priority of an existing node with the same key
+        let existing = match self.search(k.clone()) {
+            SearchResult::Exact(x) => Some(x.prio()),
+            _ => None,
+        };
+
+        //insert node with a sentil lowest priority so it's at the root
+        let (root, exists) = self.insert_node(k, None, f32::NEG_INFINITY);
 
         //remove the root node and return 2 child nodes
         let l = root.child_l();
@@ -683,7 +1838,7 @@ where
                 root.0.borrow_mut().children.0 = None;
                 x
             }
-            None => NodePtr::new(),
+            None => Self::with_aggregate(),
         };
 
         let t_r = match r {
@@ -692,10 +1847,15 @@ where
                 root.0.borrow_mut().children.1 = None;
                 x
             }
-            None => NodePtr::new(),
+            None => Self::with_aggregate(),
         };
 
+        root.pull();
+
         if exists {
+            if let Some(prio) = existing {
+                root.0.borrow_mut().prio = prio;
+            }
             ((t_l, t_r), Some(root.clone()))
         } else {
             ((t_l, t_r), None)
@@ -709,13 +1869,14 @@ where
         } else if other.is_empty() {
             self.clone()
         } else {
-            let n = NodePtr::new();
+            let n = Self::with_aggregate();
             {
                 n.0.borrow_mut().prio = f32::INFINITY;
             }
 
             n.link_left(&Some(self.clone()));
             n.link_right(&Some(other));
+            n.pull();
 
             match n.fixdown_priority() {
                 Some(_new_root) => n.remove(),
@@ -726,177 +1887,236 @@ where
         }
     }
 
+    ///helper function
+    fn non_empty(&self) -> Option<Self> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.clone())
+        }
+    }
+
+    ///helper function
+    fn or_new(x: Option<Self>) -> Self {
+        x.unwrap_or_else(Self::with_aggregate)
+    }
+
+    ///helper function
+    fn detach(&self) -> Self {
+        self.0.borrow_mut().parent = NodePtrWk(Weak::new());
+        self.clone()
+    }
+
+    ///helper function: joins 2 detached trees where keys of l < keys of r
+    fn join(l: Self, r: Self) -> Self {
+        l.merge_contiguous(r).detach()
+    }
+
     /// returns the union of 2 trees
+    /// value of self is kept for keys present in both trees
     pub fn union(&self, other: Self) -> Self {
-        if self.is_empty() && other.is_empty() {
-            return self.clone();
-        } else if self.is_empty() {
-            return other;
+        self.union_with(other, |a, _| a.clone())
+    }
+
+    /// returns the union of 2 trees
+    /// f(value of self, value of other) is used for keys present in both trees
+    pub fn union_with<F>(&self, other: Self, mut f: F) -> Self
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        self.union_impl(other, &mut f)
+    }
+
+    fn union_impl<F>(&self, other: Self, f: &mut F) -> Self
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        if self.is_empty() {
+            return other.detach();
         } else if other.is_empty() {
-            return self.clone();
+            return self.detach();
         }
 
-        let (a, b) = if self.prio() < other.prio() {
-            (self.clone(), other.clone())
+        let self_top = self.ranks_above(&other);
+
+        let (a, b) = if self_top {
+            (self.clone(), other)
         } else {
-            (other.clone(), self.clone())
+            (other, self.clone())
         };
 
-        b.0.borrow_mut().parent = NodePtrWk(Weak::new());
+        b.detach();
 
-        let k = a.key();
-        let ((t1, t2), _) = b.split_by_key(k);
+        let ((t1, t2), exists) = b.split_by_key(a.key());
 
-        let l2 = if t1.is_empty() { None } else { Some(t1) };
-        let r2 = if t2.is_empty() { None } else { Some(t2) };
-
-        let l = a.child_l();
-        let r = a.child_r();
-
-        match (&l, &l2) {
-            (Some(x), Some(y)) => {
-                let ll = x.union(y.clone());
-                if ll.is_empty() {
-                    a.link_left(&None);
-                } else {
-                    a.link_left(&Some(ll));
-                }
-            }
-            (Some(x), None) => {
-                a.link_left(&Some(x.clone()));
-            }
-            (None, Some(y)) => {
-                a.link_left(&Some(y.clone()));
-            }
-            (None, None) => {
-                a.link_left(&None);
-            }
+        if let Some(m) = exists {
+            let v = if self_top {
+                f(&a.val(), &m.val())
+            } else {
+                f(&m.val(), &a.val())
+            };
+            a.0.borrow_mut().val = Some(v);
         }
 
-        match (&r, &r2) {
-            (Some(x), Some(y)) => {
-                let rr = x.union(y.clone());
-                if rr.is_empty() {
-                    a.link_right(&None);
-                } else {
-                    a.link_right(&Some(rr));
-                }
-            }
-            (Some(x), None) => {
-                a.link_right(&Some(x.clone()));
-            }
-            (None, Some(y)) => {
-                a.link_right(&Some(y.clone()));
-            }
-            (None, None) => {
-                a.link_right(&None);
-            }
-        }
+        let l = Self::or_new(a.child_l());
+        let r = Self::or_new(a.child_r());
+
+        let (ll, rr) = if self_top {
+            (l.union_impl(t1, f), r.union_impl(t2, f))
+        } else {
+            (t1.union_impl(l, f), t2.union_impl(r, f))
+        };
 
-        a
+        a.link_left(&ll.non_empty());
+        a.link_right(&rr.non_empty());
+        a.pull();
+        a.detach()
     }
 
     /// returns the intersection of 2 trees
+    /// value of self is kept
     pub fn intersect(&self, other: Self) -> Self {
+        self.intersect_with(other, |a, _| a.clone())
+    }
+
+    /// returns the intersection of 2 trees
+    /// f(value of self, value of other) is used for the resulting values
+    pub fn intersect_with<F>(&self, other: Self, mut f: F) -> Self
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        self.intersect_impl(other, &mut f)
+    }
+
+    fn intersect_impl<F>(&self, other: Self, f: &mut F) -> Self
+    where
+        F: FnMut(&T, &T) -> T,
+    {
         if self.is_empty() || other.is_empty() {
-            return NodePtr::new();
+            return Self::with_aggregate();
         }
 
-        let (mut a, b) = if self.prio() < other.prio() {
-            (self.clone(), other.clone())
+        let self_top = self.ranks_above(&other);
+
+        let (a, b) = if self_top {
+            (self.clone(), other)
         } else {
-            (other.clone(), self.clone())
+            (other, self.clone())
         };
 
-        b.0.borrow_mut().parent = NodePtrWk(Weak::new());
+        b.detach();
 
-        let k = a.key();
-        let ((t1, t2), exists) = b.split_by_key(k);
+        let ((t1, t2), exists) = b.split_by_key(a.key());
 
-        let l2 = if t1.is_empty() { None } else { Some(t1) };
-        let r2 = if t2.is_empty() { None } else { Some(t2) };
+        let l = Self::or_new(a.child_l());
+        let r = Self::or_new(a.child_r());
 
-        let l = a.child_l();
-        let r = a.child_r();
+        let (ll, rr) = if self_top {
+            (l.intersect_impl(t1, f), r.intersect_impl(t2, f))
+        } else {
+            (t1.intersect_impl(l, f), t2.intersect_impl(r, f))
+        };
 
         match exists {
             Some(m) => {
                 //intersection of current node is non-empty
+                let v = if self_top {
+                    f(&a.val(), &m.val())
+                } else {
+                    f(&m.val(), &a.val())
+                };
+                a.0.borrow_mut().val = Some(v);
+                a.link_left(&ll.non_empty());
+                a.link_right(&rr.non_empty());
+                a.pull();
+                a.detach()
+            }
+            _ => {
+                //intersection of current node is empty
+                Self::join(ll, rr)
+            }
+        }
+    }
 
-                a = m.clone();
+    /// returns the nodes of self with keys not present in other
+    pub fn difference(&self, other: Self) -> Self {
+        if self.is_empty() {
+            return self.clone();
+        } else if other.is_empty() {
+            return self.detach();
+        }
 
-                match (&l, &l2) {
-                    (Some(x), Some(y)) => {
-                        let ll = x.intersect(y.clone());
+        if self.ranks_above(&other) {
+            other.detach();
 
-                        if ll.is_empty() {
-                            a.link_left(&None);
-                        } else {
-                            a.link_left(&Some(ll));
-                        }
-                    }
-                    _ => {
-                        a.link_left(&None);
-                    }
-                }
+            let ((t1, t2), exists) = other.split_by_key(self.key());
 
-                match (&r, &r2) {
-                    (Some(x), Some(y)) => {
-                        let rr = x.intersect(y.clone());
+            let l = Self::or_new(self.child_l());
+            let r = Self::or_new(self.child_r());
 
-                        if rr.is_empty() {
-                            a.link_right(&None);
-                        } else {
-                            a.link_right(&Some(rr));
-                        }
-                    }
-                    _ => {
-                        a.link_right(&None);
-                    }
-                }
+            let ll = l.difference(t1);
+            let rr = r.difference(t2);
 
-                a
+            match exists {
+                Some(_) => Self::join(ll, rr),
+                _ => {
+                    self.link_left(&ll.non_empty());
+                    self.link_right(&rr.non_empty());
+                    self.pull();
+                    self.detach()
+                }
             }
-            _ => {
-                //intersection of current node is empty
+        } else {
+            self.detach();
 
-                let left_branch = match (&l, &l2) {
-                    (Some(x), Some(y)) => {
-                        let ll = x.intersect(y.clone());
-                        if ll.is_empty() {
-                            None
-                        } else {
-                            Some(ll)
-                        }
-                    }
-                    _ => None,
-                };
+            //node of other is excluded from the result
+            let ((t1, t2), _) = self.split_by_key(other.key());
 
-                let right_branch = match (&r, &r2) {
-                    (Some(x), Some(y)) => {
-                        let rr = x.intersect(y.clone());
-                        if rr.is_empty() {
-                            None
-                        } else {
-                            Some(rr)
-                        }
-                    }
-                    _ => None,
-                };
+            let l = Self::or_new(other.child_l());
+            let r = Self::or_new(other.child_r());
 
-                match (left_branch, right_branch) {
-                    (Some(l), Some(r)) => l.merge_contiguous(r.clone()),
-                    (Some(l), None) => l,
-                    (None, Some(r)) => r,
-                    (None, None) => NodePtr::new(),
-                }
+            Self::join(t1.difference(l), t2.difference(r))
+        }
+    }
+
+    /// returns the nodes with keys present in exactly one of the 2 trees
+    pub fn symmetric_difference(&self, other: Self) -> Self {
+        if self.is_empty() {
+            return other.detach();
+        } else if other.is_empty() {
+            return self.detach();
+        }
+
+        let (a, b) = if self.ranks_above(&other) {
+            (self.clone(), other)
+        } else {
+            (other, self.clone())
+        };
+
+        b.detach();
+
+        let ((t1, t2), exists) = b.split_by_key(a.key());
+
+        let l = Self::or_new(a.child_l());
+        let r = Self::or_new(a.child_r());
+
+        let ll = l.symmetric_difference(t1);
+        let rr = r.symmetric_difference(t2);
+
+        match exists {
+            Some(_) => Self::join(ll, rr),
+            _ => {
+                a.link_left(&ll.non_empty());
+                a.link_right(&rr.non_empty());
+                a.pull();
+                a.detach()
             }
         }
     }
 
     /// returns min, max, avg depths
     pub fn dbg_depth(&self) -> (f32, f32, f32) {
-        if self.0.borrow().invalid {
+        if self.is_empty() {
             return (0., 0., 0.);
         }
 
@@ -951,6 +2171,151 @@ where
     }
 }
 
+impl<K, T> NodePtr<K, T, NoAgg>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    pub fn new() -> Self {
+        Self::with_aggregate()
+    }
+
+    /// builds a treap in O(n) from items with strictly increasing keys
+    pub fn from_sorted<I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = (K, T)>,
+    {
+        Self::from_sorted_with_priority_source(items, &mut PriorityRandom)
+    }
+
+    /// builds a treap in O(n) from items with strictly increasing keys,
+    /// priorities are drawn from prio_src in order of the items
+    pub fn from_sorted_with_priority_source<I, P>(items: I, prio_src: &mut P) -> Self
+    where
+        I: IntoIterator<Item = (K, T)>,
+        P: Priority<K>,
+    {
+        Self::from_sorted_with_aggregate_and_priority_source(items, prio_src)
+    }
+
+    /// builds a treap in O(n) from (key, value, priority) items with strictly increasing keys
+    pub fn from_sorted_with_priority<I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = (K, T, f32)>,
+    {
+        Self::from_sorted_with_aggregate_and_priority(items)
+    }
+}
+
+/// sorted multiset storing the number of occurrences of each key in its node,
+/// total counts of subtrees are maintained as an aggregate
+#[derive(Debug)]
+pub struct Multiset<K>
+where
+    K: Ord + Clone + Debug,
+{
+    root: NodePtr<K, i64, AggSum>,
+}
+
+impl<K> Default for Multiset<K>
+where
+    K: Ord + Clone + Debug,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> Multiset<K>
+where
+    K: Ord + Clone + Debug,
+{
+    pub fn new() -> Self {
+        Self {
+            root: NodePtr::with_aggregate(),
+        }
+    }
+
+    /// adds one occurrence of k in O(log n)
+    pub fn insert_one(&mut self, k: K) {
+        match self.root.search(k.clone()) {
+            SearchResult::Exact(x) => {
+                let c = x.val();
+                x.0.borrow_mut().val = Some(c + 1);
+                x.pull_path();
+            }
+            _ => {
+                self.root = self.root.insert(k, 1).0;
+            }
+        }
+    }
+
+    /// removes one occurrence of k in O(log n), returns false if k is absent
+    pub fn remove_one(&mut self, k: K) -> bool {
+        match self.root.search(k) {
+            SearchResult::Exact(x) => {
+                let c = x.val();
+                if c > 1 {
+                    x.0.borrow_mut().val = Some(c - 1);
+                    x.pull_path();
+                } else {
+                    self.root = x.remove();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// returns number of occurrences of k
+    pub fn count(&self, k: K) -> usize {
+        match self.root.search(k) {
+            SearchResult::Exact(x) => x.val() as usize,
+            _ => 0,
+        }
+    }
+
+    /// returns number of occurrences of keys in [k_l, k_r) in O(log n)
+    pub fn count_range(&self, k_l: K, k_r: K) -> usize {
+        self.root
+            .aggregate_range(k_l, k_r)
+            .map_or(0, |(c, _)| c as usize)
+    }
+
+    /// returns number of occurrences of all keys
+    pub fn total_len(&self) -> usize {
+        self.root.aggregate().map_or(0, |(c, _)| c as usize)
+    }
+
+    /// returns number of distinct keys
+    pub fn len(&self) -> usize {
+        self.root.aggregate().map_or(0, |(_, n)| n as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_empty()
+    }
+
+    /// returns (key, count) in key order
+    pub fn to_vec(&self) -> Vec<(K, usize)> {
+        self.root.iter().map(|(k, v)| (k, v as usize)).collect()
+    }
+
+    /// returns the union where counts of common keys are summed
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            root: self.root.union_with(other.root, |a, b| a + b),
+        }
+    }
+
+    /// returns the intersection where counts of common keys are the min of both
+    pub fn intersect(self, other: Self) -> Self {
+        Self {
+            root: self.root.intersect_with(other.root, |a, b| min(*a, *b)),
+        }
+    }
+}
+
 #[test]
 fn test_treap_search() {
     //         n0(5)
@@ -959,49 +2324,54 @@ fn test_treap_search() {
     //      / \
     //(-1)n3  n4(3)
 
-    let mut n0 = Node {
-        key: 5.,
+    let mut n0 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(5.)),
         prio: 0.,
-        val: 5,
+        val: Some(5),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
-    let mut n1 = Node {
-        key: 2.,
+    let mut n1 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(2.)),
         prio: 0.,
-        val: 2,
+        val: Some(2),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
-    let n2 = Node {
-        key: 7.,
+    let n2 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(7.)),
         prio: 0.,
-        val: 7,
+        val: Some(7),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
-    let n3 = Node {
-        key: -1.,
+    let n3 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(-1.)),
         prio: 0.,
-        val: -1,
+        val: Some(-1),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
-    let n4 = Node {
-        key: 3.,
+    let n4 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(3.)),
         prio: 0.,
-        val: 3,
+        val: Some(3),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
     let r3 = NodePtr(Rc::new(RefCell::new(n3)));
@@ -1043,33 +2413,36 @@ fn test_treap_search() {
         }
     }
 
-    match r0.search(3.) {
+    match r0.search(OrdF32(3.)) {
         SearchResult::Exact(x) => {
-            assert!(equal_f32(x.0.borrow().key, 4.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, 4.));
 
-            assert!(equal_f32(x.get_root().0.borrow().key, 5.));
+            assert!(equal_f32(x.get_root().0.borrow().key.unwrap().0, 5.));
         }
         _ => {
             panic!();
         }
     }
 
-    match r0.search(4.) {
+    match r0.search(OrdF32(4.)) {
         SearchResult::Nearest(x) => {
-            assert!(equal_f32(x.0.borrow().key, 4.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, 4.));
         }
         _ => {
             panic!();
         }
     }
 
-    match r0.search(2.) {
+    match r0.search(OrdF32(2.)) {
         SearchResult::Exact(x) => {
-            assert!(equal_f32(x.0.borrow().key, 2.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, 2.));
 
             match x.child_branch(&x.child_r()) {
                 ChildBranch::Right => {
-                    assert!(equal_f32(x.child_r().unwrap().0.borrow().key, 3.));
+                    assert!(equal_f32(
+                        x.child_r().unwrap().0.borrow().key.unwrap().0,
+                        3.
+                    ));
                 }
                 _ => {
                     panic!("incorrect child branch");
@@ -1081,55 +2454,55 @@ fn test_treap_search() {
         }
     }
 
-    match r0.search(-1.) {
+    match r0.search(OrdF32(-1.)) {
         SearchResult::Exact(x) => {
-            assert!(equal_f32(x.0.borrow().key, -1.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, -1.));
         }
         _ => {
             panic!();
         }
     }
-    match r0.search(-10.) {
+    match r0.search(OrdF32(-10.)) {
         SearchResult::Nearest(x) => {
-            assert!(equal_f32(x.0.borrow().key, -1.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, -1.));
         }
         _ => {
             panic!();
         }
     }
-    match r0.search(5.) {
+    match r0.search(OrdF32(5.)) {
         SearchResult::Exact(x) => {
-            assert!(equal_f32(x.0.borrow().key, 5.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, 5.));
         }
         _ => {
             panic!();
         }
     }
 
-    match r0.search(6.) {
+    match r0.search(OrdF32(6.)) {
         SearchResult::Nearest(x) => {
-            assert!(equal_f32(x.0.borrow().key, 5.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, 5.));
         }
         _ => {
             panic!();
         }
     }
 
-    match r0.search(10.) {
+    match r0.search(OrdF32(10.)) {
         SearchResult::Nearest(x) => {
-            assert!(equal_f32(x.0.borrow().key, 7.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, 7.));
         }
         _ => {
             panic!();
         }
     }
 
-    match r0.search(2.) {
-        SearchResult::Exact(x) => match x.search(-10.) {
+    match r0.search(OrdF32(2.)) {
+        SearchResult::Exact(x) => match x.search(OrdF32(-10.)) {
             SearchResult::Nearest(y) => {
-                assert!(equal_f32(y.0.borrow().key, -1.));
-                assert_eq!(y.0.borrow().val, -1);
-                y.0.borrow_mut().val = 100;
+                assert!(equal_f32(y.0.borrow().key.unwrap().0, -1.));
+                assert_eq!(y.0.borrow().val, Some(-1));
+                y.0.borrow_mut().val = Some(100);
             }
             _ => {
                 panic!();
@@ -1140,10 +2513,10 @@ fn test_treap_search() {
         }
     }
 
-    match r0.search(-1.) {
+    match r0.search(OrdF32(-1.)) {
         SearchResult::Exact(x) => {
-            assert!(equal_f32(x.0.borrow().key, -1.));
-            assert_eq!(x.0.borrow().val, 100);
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, -1.));
+            assert_eq!(x.0.borrow().val, Some(100));
 
             let parent_key =
                 x.0.borrow()
@@ -1152,7 +2525,9 @@ fn test_treap_search() {
                     .upgrade()
                     .expect("parent invalid")
                     .borrow()
-                    .key;
+                    .key
+                    .unwrap()
+                    .0;
             assert!(equal_f32(parent_key, 2.));
         }
         _ => {
@@ -1169,49 +2544,54 @@ fn test_treap_rotate_0() {
     //      / \
     //(-1)n3  n4(3)
 
-    let mut n0 = Node {
-        key: 5.,
+    let mut n0 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(5.)),
         prio: 0.,
-        val: 5,
+        val: Some(5),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
-    let mut n1 = Node {
-        key: 2.,
+    let mut n1 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(2.)),
         prio: 0.,
-        val: 2,
+        val: Some(2),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
-    let n2 = Node {
-        key: 7.,
+    let n2 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(7.)),
         prio: 0.,
-        val: 7,
+        val: Some(7),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
-    let n3 = Node {
-        key: -1.,
+    let n3 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(-1.)),
         prio: 0.,
-        val: -1,
+        val: Some(-1),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
-    let n4 = Node {
-        key: 3.,
+    let n4 = Node::<_, _, NoAgg> {
+        key: Some(OrdF32(3.)),
         prio: 0.,
-        val: 3,
+        val: Some(3),
         parent: Default::default(),
         children: (None, None),
-        invalid: false,
+        agg: None,
+        tag: None,
     };
 
     let r3 = NodePtr(Rc::new(RefCell::new(n3)));
@@ -1247,9 +2627,9 @@ fn test_treap_rotate_0() {
     }
 
     //left rotate up node with key=2
-    match r0.search(2.) {
+    match r0.search(OrdF32(2.)) {
         SearchResult::Exact(x) => {
-            assert!(equal_f32(x.0.borrow().key, 2.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, 2.));
 
             let parent_key =
                 x.0.borrow()
@@ -1258,7 +2638,9 @@ fn test_treap_rotate_0() {
                     .upgrade()
                     .expect("parent invalid")
                     .borrow()
-                    .key;
+                    .key
+                    .unwrap()
+                    .0;
             assert!(equal_f32(parent_key, 5.));
 
             x.rot_up_left();
@@ -1266,7 +2648,16 @@ fn test_treap_rotate_0() {
             assert!(x.0.borrow().parent.0.upgrade().is_none());
 
             assert!(equal_f32(
-                x.0.borrow().children.1.as_ref().unwrap().0.borrow().key,
+                x.0.borrow()
+                    .children
+                    .1
+                    .as_ref()
+                    .unwrap()
+                    .0
+                    .borrow()
+                    .key
+                    .unwrap()
+                    .0,
                 5.
             ));
 
@@ -1284,7 +2675,9 @@ fn test_treap_rotate_0() {
                     .unwrap()
                     .0
                     .borrow()
-                    .key;
+                    .key
+                    .unwrap()
+                    .0;
 
             assert!(equal_f32(x_r_l_key, 3.));
         }
@@ -1303,49 +2696,54 @@ fn test_treap_rotate_1() {
     //(-1)n3  n4(3)
 
     let r0 = {
-        let mut n0 = Node {
-            key: 5.,
+        let mut n0 = Node::<_, _, NoAgg> {
+            key: Some(OrdF32(5.)),
             prio: 0.,
-            val: 5,
+            val: Some(5),
             parent: Default::default(),
             children: (None, None),
-            invalid: false,
+            agg: None,
+            tag: None,
         };
 
-        let mut n1 = Node {
-            key: 2.,
+        let mut n1 = Node::<_, _, NoAgg> {
+            key: Some(OrdF32(2.)),
             prio: 0.,
-            val: 2,
+            val: Some(2),
             parent: Default::default(),
             children: (None, None),
-            invalid: false,
+            agg: None,
+            tag: None,
         };
 
-        let n2 = Node {
-            key: 7.,
+        let n2 = Node::<_, _, NoAgg> {
+            key: Some(OrdF32(7.)),
             prio: 0.,
-            val: 7,
+            val: Some(7),
             parent: Default::default(),
             children: (None, None),
-            invalid: false,
+            agg: None,
+            tag: None,
         };
 
-        let n3 = Node {
-            key: -1.,
+        let n3 = Node::<_, _, NoAgg> {
+            key: Some(OrdF32(-1.)),
             prio: 0.,
-            val: -1,
+            val: Some(-1),
             parent: Default::default(),
             children: (None, None),
-            invalid: false,
+            agg: None,
+            tag: None,
         };
 
-        let n4 = Node {
-            key: 3.,
+        let n4 = Node::<_, _, NoAgg> {
+            key: Some(OrdF32(3.)),
             prio: 0.,
-            val: 3,
+            val: Some(3),
             parent: Default::default(),
             children: (None, None),
-            invalid: false,
+            agg: None,
+            tag: None,
         };
 
         let r3 = NodePtr(Rc::new(RefCell::new(n3)));
@@ -1384,9 +2782,9 @@ fn test_treap_rotate_1() {
     }
 
     //left rotate up node with key=-1
-    match r0.search(-1.) {
+    match r0.search(OrdF32(-1.)) {
         SearchResult::Exact(x) => {
-            assert!(equal_f32(x.0.borrow().key, -1.));
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, -1.));
 
             let parent_key =
                 x.0.borrow()
@@ -1395,7 +2793,9 @@ fn test_treap_rotate_1() {
                     .upgrade()
                     .expect("parent invalid")
                     .borrow()
-                    .key;
+                    .key
+                    .unwrap()
+                    .0;
             assert!(equal_f32(parent_key, 2.));
 
             x.rot_up_left();
@@ -1407,11 +2807,23 @@ fn test_treap_rotate_1() {
                     .upgrade()
                     .expect("parent after rotate")
                     .borrow()
-                    .key,
+                    .key
+                    .unwrap()
+                    .0,
                 5.
             ));
 
-            let x_r_key = x.0.borrow().children.1.as_ref().unwrap().0.borrow().key;
+            let x_r_key =
+                x.0.borrow()
+                    .children
+                    .1
+                    .as_ref()
+                    .unwrap()
+                    .0
+                    .borrow()
+                    .key
+                    .unwrap()
+                    .0;
 
             assert!(equal_f32(x_r_key, 2.));
         }
@@ -1448,21 +2860,21 @@ fn test_treap_insert_with_priority() {
 
     let t = NodePtr::new();
 
-    let (t1, _) = t.insert_with_priority(3., 3, 50.);
+    let (t1, _) = t.insert_with_priority(OrdF32(3.), 3, 50.);
 
-    assert!(equal_f32(t1.0.borrow().key, 3.));
+    assert!(equal_f32(t1.0.borrow().key.unwrap().0, 3.));
 
-    let (t2, _) = t1.insert_with_priority(1., 1, 75.);
+    let (t2, _) = t1.insert_with_priority(OrdF32(1.), 1, 75.);
 
-    assert!(equal_f32(t2.0.borrow().key, 3.));
+    assert!(equal_f32(t2.0.borrow().key.unwrap().0, 3.));
 
-    let (t3, _) = t2.insert_with_priority(8., 8, 30.);
+    let (t3, _) = t2.insert_with_priority(OrdF32(8.), 8, 30.);
 
-    assert!(equal_f32(t3.0.borrow().key, 8.));
+    assert!(equal_f32(t3.0.borrow().key.unwrap().0, 8.));
 
-    let (t4, _) = t3.insert_with_priority(6., 6, -20.);
+    let (t4, _) = t3.insert_with_priority(OrdF32(6.), 6, -20.);
 
-    assert!(equal_f32(t4.0.borrow().key, 6.));
+    assert!(equal_f32(t4.0.borrow().key.unwrap().0, 6.));
 }
 
 #[test]
@@ -1503,33 +2915,33 @@ fn test_treap_insert_with_priority_replacement() {
 
     let t = NodePtr::new();
 
-    let (t1, _) = t.insert_with_priority(3., 3, 50.);
+    let (t1, _) = t.insert_with_priority(OrdF32(3.), 3, 50.);
 
-    assert!(equal_f32(t1.0.borrow().key, 3.));
+    assert!(equal_f32(t1.0.borrow().key.unwrap().0, 3.));
 
-    let (t2, _) = t1.insert_with_priority(1., 1, 75.);
+    let (t2, _) = t1.insert_with_priority(OrdF32(1.), 1, 75.);
 
-    assert!(equal_f32(t2.0.borrow().key, 3.));
+    assert!(equal_f32(t2.0.borrow().key.unwrap().0, 3.));
 
-    let (t3, _) = t2.insert_with_priority(1., 1, 60.);
+    let (t3, _) = t2.insert_with_priority(OrdF32(1.), 1, 60.);
 
-    assert!(equal_f32(t3.0.borrow().key, 3.));
+    assert!(equal_f32(t3.0.borrow().key.unwrap().0, 3.));
 
-    let (t4, _) = t3.insert_with_priority(8., 8, 30.);
+    let (t4, _) = t3.insert_with_priority(OrdF32(8.), 8, 30.);
 
-    assert!(equal_f32(t4.0.borrow().key, 8.));
+    assert!(equal_f32(t4.0.borrow().key.unwrap().0, 8.));
 
-    let (t5, _) = t4.insert_with_priority(6., 6, -20.);
+    let (t5, _) = t4.insert_with_priority(OrdF32(6.), 6, -20.);
 
-    assert!(equal_f32(t5.0.borrow().key, 6.));
+    assert!(equal_f32(t5.0.borrow().key.unwrap().0, 6.));
 
-    let (t6, _) = t5.insert_with_priority(6., 6, 100.);
+    let (t6, _) = t5.insert_with_priority(OrdF32(6.), 6, 100.);
 
-    assert!(equal_f32(t6.0.borrow().key, 8.));
+    assert!(equal_f32(t6.0.borrow().key.unwrap().0, 8.));
 
-    let (t7, _) = t6.insert_with_priority(1., 1, 0.);
+    let (t7, _) = t6.insert_with_priority(OrdF32(1.), 1, 0.);
 
-    assert!(equal_f32(t7.0.borrow().key, 1.));
+    assert!(equal_f32(t7.0.borrow().key.unwrap().0, 1.));
 }
 
 #[test]
@@ -1559,19 +2971,19 @@ fn test_treap_successor() {
 
     let t = NodePtr::new();
 
-    let (t1, _) = t.insert_with_priority(3., 3, 50.);
-    let (t2, _) = t1.insert_with_priority(1., 1, 75.);
-    let (t3, _) = t2.insert_with_priority(8., 8, 30.);
-    let (t4, _) = t3.insert_with_priority(6., 6, -20.);
+    let (t1, _) = t.insert_with_priority(OrdF32(3.), 3, 50.);
+    let (t2, _) = t1.insert_with_priority(OrdF32(1.), 1, 75.);
+    let (t3, _) = t2.insert_with_priority(OrdF32(8.), 8, 30.);
+    let (t4, _) = t3.insert_with_priority(OrdF32(6.), 6, -20.);
 
-    match t4.search(1.) {
+    match t4.search(OrdF32(1.)) {
         SearchResult::Exact(x) => {
-            assert!(equal_f32(x.0.borrow().key, 1.));
-            let mut keys = vec![x.key()];
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, 1.));
+            let mut keys = vec![x.key().0];
 
             let mut cur = x;
             while let Some(y) = cur.successor() {
-                keys.push(y.key());
+                keys.push(y.key().0);
                 cur = y;
             }
 
@@ -1604,20 +3016,20 @@ fn test_treap_predecessor() {
 
     let t = NodePtr::new();
 
-    let (t1, _) = t.insert_with_priority(3., 3, 50.);
-    let (t2, _) = t1.insert_with_priority(1., 1, 75.);
-    let (t3, _) = t2.insert_with_priority(8., 8, 30.);
-    let (t4, _) = t3.insert_with_priority(6., 6, -20.);
-    let (t5, _) = t4.insert_with_priority(7., 7, 100.);
+    let (t1, _) = t.insert_with_priority(OrdF32(3.), 3, 50.);
+    let (t2, _) = t1.insert_with_priority(OrdF32(1.), 1, 75.);
+    let (t3, _) = t2.insert_with_priority(OrdF32(8.), 8, 30.);
+    let (t4, _) = t3.insert_with_priority(OrdF32(6.), 6, -20.);
+    let (t5, _) = t4.insert_with_priority(OrdF32(7.), 7, 100.);
 
-    match t5.search(8.) {
+    match t5.search(OrdF32(8.)) {
         SearchResult::Exact(x) => {
-            assert!(equal_f32(x.0.borrow().key, 8.));
-            let mut keys = vec![x.key()];
+            assert!(equal_f32(x.0.borrow().key.unwrap().0, 8.));
+            let mut keys = vec![x.key().0];
 
             let mut cur = x;
             while let Some(y) = cur.predecessor() {
-                keys.push(y.key());
+                keys.push(y.key().0);
                 cur = y;
             }
 
@@ -1652,17 +3064,17 @@ fn test_treap_query_key_range() {
 
     let t = NodePtr::new();
 
-    let (t1, _) = t.insert_with_priority(3., 3, 50.);
-    let (t2, _) = t1.insert_with_priority(1., 1, 75.);
-    let (t3, _) = t2.insert_with_priority(8., 8, 30.);
-    let (t4, _) = t3.insert_with_priority(6., 6, -20.);
-    let (t5, _) = t4.insert_with_priority(7., 7, 100.);
+    let (t1, _) = t.insert_with_priority(OrdF32(3.), 3, 50.);
+    let (t2, _) = t1.insert_with_priority(OrdF32(1.), 1, 75.);
+    let (t3, _) = t2.insert_with_priority(OrdF32(8.), 8, 30.);
+    let (t4, _) = t3.insert_with_priority(OrdF32(6.), 6, -20.);
+    let (t5, _) = t4.insert_with_priority(OrdF32(7.), 7, 100.);
 
     {
         let v = t5
-            .query_key_range(-10., 10.)
+            .query_key_range(OrdF32(-10.), OrdF32(10.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), items.len());
@@ -1674,9 +3086,9 @@ fn test_treap_query_key_range() {
 
     {
         let v = t5
-            .query_key_range(1., 2.)
+            .query_key_range(OrdF32(1.), OrdF32(2.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         let expected = items
@@ -1695,9 +3107,9 @@ fn test_treap_query_key_range() {
 
     {
         let v = t5
-            .query_key_range(1., 1.)
+            .query_key_range(OrdF32(1.), OrdF32(1.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), 0);
@@ -1705,9 +3117,9 @@ fn test_treap_query_key_range() {
 
     {
         let v = t5
-            .query_key_range(2., 7.5)
+            .query_key_range(OrdF32(2.), OrdF32(7.5))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         let expected = items
@@ -1752,20 +3164,20 @@ fn test_treap_remove() {
 
     let t5 = {
         let t = NodePtr::new();
-        let (t1, _) = t.insert_with_priority(3., 3, 50.);
-        let (t2, _) = t1.insert_with_priority(1., 1, 75.);
-        let (t3, _) = t2.insert_with_priority(8., 8, 30.);
-        let (t4, _) = t3.insert_with_priority(6., 6, -20.);
-        t4.insert_with_priority(7., 7, 100.).0
+        let (t1, _) = t.insert_with_priority(OrdF32(3.), 3, 50.);
+        let (t2, _) = t1.insert_with_priority(OrdF32(1.), 1, 75.);
+        let (t3, _) = t2.insert_with_priority(OrdF32(8.), 8, 30.);
+        let (t4, _) = t3.insert_with_priority(OrdF32(6.), 6, -20.);
+        t4.insert_with_priority(OrdF32(7.), 7, 100.).0
     };
 
-    assert!(equal_f32(t5.key(), 6.));
+    assert!(equal_f32(t5.key().0, 6.));
 
     {
         let v = t5
-            .query_key_range(-10., 10.)
+            .query_key_range(OrdF32(-10.), OrdF32(10.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), items.len());
@@ -1775,20 +3187,20 @@ fn test_treap_remove() {
             .for_each(|(a, b)| assert!(equal_f32(*a as f32, *b)));
     }
 
-    let t6 = match t5.search(6.) {
+    let t6 = match t5.search(OrdF32(6.)) {
         SearchResult::Exact(x) => x.remove(),
         _ => {
             panic!("search unexpected result");
         }
     };
 
-    assert!(equal_f32(t6.key(), 8.));
+    assert!(equal_f32(t6.key().0, 8.));
 
     {
         let v = t6
-            .query_key_range(-10., 10.)
+            .query_key_range(OrdF32(-10.), OrdF32(10.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         let expected = items
@@ -1805,32 +3217,32 @@ fn test_treap_remove() {
 
     let t7 = t6.remove();
 
-    assert!(equal_f32(t7.key(), 3.));
+    assert!(equal_f32(t7.key().0, 3.));
 
     let t8 = t7.remove();
 
-    assert!(equal_f32(t8.key(), 1.));
+    assert!(equal_f32(t8.key().0, 1.));
 
     let t9 = t8.remove();
 
-    assert!(equal_f32(t9.key(), 7.));
+    assert!(equal_f32(t9.key().0, 7.));
 
     let t10 = t9.remove();
 
-    assert_eq!(true, t10.0.borrow().invalid);
+    assert_eq!(true, t10.is_empty());
 
     //query on empty tree
     {
-        match t10.search(0.) {
+        match t10.search(OrdF32(0.)) {
             SearchResult::Empty => {}
             _ => {
                 panic!("unexpected search result");
             }
         }
         let v = t10
-            .query_key_range(-10., 10.)
+            .query_key_range(OrdF32(-10.), OrdF32(10.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), 0);
@@ -1851,9 +3263,9 @@ fn test_treap_insert_remove_by_key() {
 
     {
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), 0);
@@ -1861,7 +3273,7 @@ fn test_treap_insert_remove_by_key() {
 
     let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
     for i in items.iter() {
-        t = t.insert(*i as f32, *i).0;
+        t = t.insert(OrdF32(*i as f32), *i).0;
     }
 
     let mut expected = items.clone();
@@ -1869,9 +3281,9 @@ fn test_treap_insert_remove_by_key() {
 
     {
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), expected.len());
@@ -1884,16 +3296,16 @@ fn test_treap_insert_remove_by_key() {
 
     let l = items.len() / 2;
     for i in expected.iter().take(l) {
-        t = t.remove_by_key(*i as f32);
+        t = t.remove_by_key(OrdF32(*i as f32));
     }
 
     {
         let f = expected.iter().skip(l).cloned().collect::<Vec<_>>();
 
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), f.len());
@@ -1918,9 +3330,9 @@ fn test_treap_remove_key_range() {
 
     {
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), 0);
@@ -1928,7 +3340,7 @@ fn test_treap_remove_key_range() {
 
     let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
     for i in items.iter() {
-        t = t.insert(*i as f32, *i).0;
+        t = t.insert(OrdF32(*i as f32), *i).0;
     }
 
     let mut expected = items.clone();
@@ -1936,9 +3348,9 @@ fn test_treap_remove_key_range() {
 
     {
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), expected.len());
@@ -1949,7 +3361,7 @@ fn test_treap_remove_key_range() {
             .for_each(|(a, b)| assert!(equal_f32(*a as f32, *b)));
     }
 
-    t = t.remove_by_key_range(-9., 56.);
+    t = t.remove_by_key_range(OrdF32(-9.), OrdF32(56.));
 
     {
         let f = expected
@@ -1959,9 +3371,9 @@ fn test_treap_remove_key_range() {
             .collect::<Vec<_>>();
 
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), f.len());
@@ -1986,17 +3398,24 @@ fn test_treap_split_by_key() {
 
     {
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), 0);
     }
 
+    //splitting an empty tree leaves it empty
+    {
+        let ((t1, t2), m) = t.split_by_key(OrdF32(0.));
+        assert!(t1.is_empty() && t2.is_empty() && m.is_none());
+        assert!(t.is_empty());
+    }
+
     let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
     for i in items.iter() {
-        t = t.insert(*i as f32, *i).0;
+        t = t.insert(OrdF32(*i as f32), *i).0;
     }
 
     let mut expected = items.clone();
@@ -2004,9 +3423,9 @@ fn test_treap_split_by_key() {
 
     {
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), expected.len());
@@ -2017,7 +3436,7 @@ fn test_treap_split_by_key() {
             .for_each(|(a, b)| assert!(equal_f32(*a as f32, *b)));
     }
 
-    let ((t1, t2), _) = t.split_by_key(0.);
+    let ((t1, t2), _) = t.split_by_key(OrdF32(0.));
 
     {
         let f = expected
@@ -2027,9 +3446,9 @@ fn test_treap_split_by_key() {
             .collect::<Vec<_>>();
 
         let v = t1
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), f.len());
@@ -2047,9 +3466,9 @@ fn test_treap_split_by_key() {
             .collect::<Vec<_>>();
 
         let v = t2
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), f.len());
@@ -2074,9 +3493,9 @@ fn test_treap_split_merge() {
 
     {
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), 0);
@@ -2084,7 +3503,7 @@ fn test_treap_split_merge() {
 
     let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
     for i in items.iter() {
-        t = t.insert(*i as f32, *i).0;
+        t = t.insert(OrdF32(*i as f32), *i).0;
     }
 
     let mut expected = items.clone();
@@ -2092,9 +3511,9 @@ fn test_treap_split_merge() {
 
     {
         let v = t
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), expected.len());
@@ -2105,15 +3524,15 @@ fn test_treap_split_merge() {
             .for_each(|(a, b)| assert!(equal_f32(*a as f32, *b)));
     }
 
-    let ((t1, t2), _) = t.split_by_key(0.);
+    let ((t1, t2), _) = t.split_by_key(OrdF32(0.));
 
     let t3 = t1.merge_contiguous(t2);
 
     {
         let v = t3
-            .query_key_range(-100., 100.)
+            .query_key_range(OrdF32(-100.), OrdF32(100.))
             .iter()
-            .map(|x| x.key())
+            .map(|x| x.key().0)
             .collect::<Vec<_>>();
 
         assert_eq!(v.len(), expected.len());
@@ -2139,7 +3558,7 @@ fn test_treap_depth() {
     let mut t = NodePtr::new();
 
     for i in nums.iter() {
-        t = t.insert(*i, 0i32).0;
+        t = t.insert(OrdF32(*i), 0i32).0;
     }
 
     let (d_min, d_max, d_avg) = t.dbg_depth();
@@ -2167,13 +3586,13 @@ fn test_treap_insert_remove_stress() {
     let t0 = Local::now();
 
     for i in nums.iter() {
-        t = t.insert(*i, 0i32).0;
+        t = t.insert(OrdF32(*i), 0i32).0;
     }
 
     let t1 = Local::now();
 
     for i in nums.iter() {
-        t = t.remove_by_key(*i);
+        t = t.remove_by_key(OrdF32(*i));
     }
 
     let t2 = Local::now();
@@ -2203,12 +3622,12 @@ fn test_treap_insert_remove_range_stress() {
     let t0 = Local::now();
 
     for i in nums.iter() {
-        t = t.insert(*i, 0i32).0;
+        t = t.insert(OrdF32(*i), 0i32).0;
     }
 
     let t1 = Local::now();
 
-    t = t.remove_by_key_range(-1e10, 1e10);
+    t = t.remove_by_key_range(OrdF32(-1e10), OrdF32(1e10));
 
     let t2 = Local::now();
 
@@ -2232,7 +3651,7 @@ fn test_treap_union_empty() {
     }
 
     {
-        let t1: NodePtr<f32, i32> = NodePtr::new();
+        let t1: NodePtr<OrdF32, i32> = NodePtr::new();
         let t2 = NodePtr::new();
         let t3 = t1.union(t2);
         assert!(t3.is_empty());
@@ -2247,16 +3666,16 @@ fn test_treap_union_empty() {
         let t2 = NodePtr::new();
 
         for i in va.iter() {
-            t1 = t1.insert(*i as f32, *i).0;
+            t1 = t1.insert(OrdF32(*i as f32), *i).0;
         }
 
         let t3 = t1.union(t2);
 
         {
             let v = t3
-                .query_key_range(-1e10, 1e10)
+                .query_key_range(OrdF32(-1e10), OrdF32(1e10))
                 .iter()
-                .map(|x| x.key())
+                .map(|x| x.key().0)
                 .collect::<Vec<_>>();
 
             assert_eq!(v.len(), va.len());
@@ -2276,16 +3695,16 @@ fn test_treap_union_empty() {
         let mut t2 = NodePtr::new();
 
         for i in vb.iter() {
-            t2 = t2.insert(*i as f32, *i).0;
+            t2 = t2.insert(OrdF32(*i as f32), *i).0;
         }
 
         let t3 = t1.union(t2);
 
         {
             let v = t3
-                .query_key_range(-1e10, 1e10)
+                .query_key_range(OrdF32(-1e10), OrdF32(1e10))
                 .iter()
-                .map(|x| x.key())
+                .map(|x| x.key().0)
                 .collect::<Vec<_>>();
 
             assert_eq!(v.len(), vb.len());
@@ -2375,7 +3794,7 @@ fn test_treap_union_nonempty() {
 #[test]
 fn test_treap_intersect_empty() {
     {
-        let t1: NodePtr<f32, i32> = NodePtr::new();
+        let t1: NodePtr<OrdF32, i32> = NodePtr::new();
         let t2 = NodePtr::new();
         let t3 = t1.intersect(t2);
         assert!(t3.is_empty());
@@ -2390,16 +3809,16 @@ fn test_treap_intersect_empty() {
         let t2 = NodePtr::new();
 
         for i in va.iter() {
-            t1 = t1.insert(*i as f32, *i).0;
+            t1 = t1.insert(OrdF32(*i as f32), *i).0;
         }
 
         let t3 = t1.intersect(t2);
 
         {
             let v = t3
-                .query_key_range(-1e10, 1e10)
+                .query_key_range(OrdF32(-1e10), OrdF32(1e10))
                 .iter()
-                .map(|x| x.key())
+                .map(|x| x.key().0)
                 .collect::<Vec<_>>();
 
             assert_eq!(v.len(), 0);
@@ -2415,16 +3834,16 @@ fn test_treap_intersect_empty() {
         let mut t2 = NodePtr::new();
 
         for i in vb.iter() {
-            t2 = t2.insert(*i as f32, *i).0;
+            t2 = t2.insert(OrdF32(*i as f32), *i).0;
         }
 
         let t3 = t1.intersect(t2);
 
         {
             let v = t3
-                .query_key_range(-1e10, 1e10)
+                .query_key_range(OrdF32(-1e10), OrdF32(1e10))
                 .iter()
-                .map(|x| x.key())
+                .map(|x| x.key().0)
                 .collect::<Vec<_>>();
 
             assert_eq!(v.len(), 0);
@@ -2591,3 +4010,1045 @@ fn test_treap_intersect_nonempty_2() {
     let t_elapse = ck2.signed_duration_since(ck1).num_microseconds().unwrap() as f64;
     println!("intersect of sizes({},{}): {} us", count, count, t_elapse);
 }
+
+#[test]
+fn test_treap_difference() {
+    let va = (0..200).map(|x| x * 2).collect::<Vec<i32>>();
+    let vb = (0..100).map(|x| x * 3).collect::<Vec<i32>>();
+
+    let mut t1: NodePtr<i32, i32> = NodePtr::new();
+    let mut t2: NodePtr<i32, i32> = NodePtr::new();
+
+    for i in va.iter() {
+        t1 = t1.insert(*i, *i).0;
+    }
+
+    for i in vb.iter() {
+        t2 = t2.insert(*i, -*i).0;
+    }
+
+    let t3 = t1.difference(t2);
+
+    let v = t3
+        .query_key_range(-10_000_000, 10_000_000)
+        .iter()
+        .map(|x| (x.key(), x.val()))
+        .collect::<Vec<_>>();
+
+    let expected = va
+        .iter()
+        .cloned()
+        .filter(|x| !vb.contains(x))
+        .map(|x| (x, x))
+        .collect::<Vec<_>>();
+
+    assert_eq!(v, expected);
+
+    {
+        let t4: NodePtr<i32, i32> = NodePtr::new();
+        let t5 = t4.difference(t3.clone());
+        assert!(t5.is_empty());
+    }
+}
+
+#[test]
+fn test_treap_symmetric_difference() {
+    let va = (0..200).map(|x| x * 2).collect::<Vec<i32>>();
+    let vb = (0..100).map(|x| x * 3).collect::<Vec<i32>>();
+
+    let mut t1: NodePtr<i32, i32> = NodePtr::new();
+    let mut t2: NodePtr<i32, i32> = NodePtr::new();
+
+    for i in va.iter() {
+        t1 = t1.insert(*i, *i).0;
+    }
+
+    for i in vb.iter() {
+        t2 = t2.insert(*i, *i).0;
+    }
+
+    let t3 = t1.symmetric_difference(t2);
+
+    let v = t3
+        .query_key_range(-10_000_000, 10_000_000)
+        .iter()
+        .map(|x| x.key())
+        .collect::<Vec<_>>();
+
+    let mut expected = va
+        .iter()
+        .cloned()
+        .filter(|x| !vb.contains(x))
+        .chain(vb.iter().cloned().filter(|x| !va.contains(x)))
+        .collect::<Vec<_>>();
+    expected.sort();
+
+    assert_eq!(v, expected);
+}
+
+#[test]
+fn test_treap_union_intersect_with() {
+    let va = (0..200).map(|x| x * 2).collect::<Vec<i32>>();
+    let vb = (0..100).map(|x| x * 3).collect::<Vec<i32>>();
+
+    let build = |items: &[i32], mult: i32| {
+        let mut t: NodePtr<i32, i32> = NodePtr::new();
+        for i in items.iter() {
+            t = t.insert(*i, *i * mult).0;
+        }
+        t
+    };
+
+    {
+        let t3 = build(&va[..], 1).union_with(build(&vb[..], 10), |a, b| a + b);
+
+        let v = t3
+            .query_key_range(-10_000_000, 10_000_000)
+            .iter()
+            .map(|x| (x.key(), x.val()))
+            .collect::<Vec<_>>();
+
+        let mut expected = va.iter().chain(vb.iter()).cloned().collect::<Vec<_>>();
+        expected.sort();
+        expected.dedup();
+        let expected = expected
+            .into_iter()
+            .map(|x| match (x % 2 == 0 && x < 400, x % 3 == 0 && x < 300) {
+                (true, true) => (x, x + x * 10),
+                (true, false) => (x, x),
+                _ => (x, x * 10),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(v, expected);
+    }
+
+    {
+        let t3 = build(&va[..], 1).intersect_with(build(&vb[..], 10), |a, b| a - b);
+
+        let v = t3
+            .query_key_range(-10_000_000, 10_000_000)
+            .iter()
+            .map(|x| (x.key(), x.val()))
+            .collect::<Vec<_>>();
+
+        let expected = vb
+            .iter()
+            .cloned()
+            .filter(|x| x % 2 == 0)
+            .map(|x| (x, x - x * 10))
+            .collect::<Vec<_>>();
+
+        assert_eq!(v, expected);
+    }
+
+    {
+        //plain intersect keeps the value of self
+        let t3 = build(&vb[..], 10).intersect(build(&va[..], 1));
+
+        let v = t3
+            .query_key_range(-10_000_000, 10_000_000)
+            .iter()
+            .map(|x| (x.key(), x.val()))
+            .collect::<Vec<_>>();
+
+        let expected = vb
+            .iter()
+            .cloned()
+            .filter(|x| x % 2 == 0)
+            .map(|x| (x, x * 10))
+            .collect::<Vec<_>>();
+
+        assert_eq!(v, expected);
+    }
+}
+
+#[test]
+fn test_treap_priority_source() {
+    let items = (0..1000).map(|x| (x * 7919) % 1000).collect::<Vec<i32>>();
+
+    fn shape(t: &NodePtr<i32, i32>) -> Vec<(i32, Option<i32>, Option<i32>)> {
+        t.query_key_range(-1_000_000, 1_000_000)
+            .iter()
+            .map(|x| {
+                (
+                    x.key(),
+                    x.child_l().map(|y| y.key()),
+                    x.child_r().map(|y| y.key()),
+                )
+            })
+            .collect()
+    }
+
+    //seeded priorities produce identical trees
+    {
+        let build = |seed: u64| {
+            let mut src = PrioritySeeded::new(seed);
+            let mut t = NodePtr::new();
+            for i in items.iter() {
+                t = t.insert_with_priority_source(*i, *i, &mut src).0;
+            }
+            t
+        };
+
+        let t1 = build(7);
+        let t2 = build(7);
+
+        assert_eq!(t1.key(), t2.key());
+        assert_eq!(shape(&t1), shape(&t2));
+        assert_eq!(t1.dbg_depth(), t2.dbg_depth());
+    }
+
+    //hashed priorities produce identical trees regardless of insertion order
+    {
+        let mut t1 = NodePtr::new();
+        for i in items.iter() {
+            t1 = t1.insert_with_priority_source(*i, *i, &mut PriorityHash).0;
+        }
+
+        let mut t2 = NodePtr::new();
+        for i in items.iter().rev() {
+            t2 = t2.insert_with_priority_source(*i, *i, &mut PriorityHash).0;
+        }
+
+        assert_eq!(shape(&t1), shape(&t2));
+        assert_eq!(t1.dbg_depth(), t2.dbg_depth());
+    }
+
+    //equal priorities are ordered by key regardless of insertion order
+    {
+        let mut f = |k: &i32| (*k % 4) as f32;
+
+        let mut t1 = NodePtr::new();
+        for i in items.iter() {
+            t1 = t1.insert_with_priority_source(*i, *i, &mut f).0;
+        }
+
+        let mut t2 = NodePtr::new();
+        for i in items.iter().rev() {
+            t2 = t2.insert_with_priority_source(*i, *i, &mut f).0;
+        }
+
+        assert_eq!(shape(&t1), shape(&t2));
+        assert_eq!(t1.dbg_depth(), t2.dbg_depth());
+    }
+
+    //user supplied priorities
+    {
+        let mut t = NodePtr::new();
+        let mut f = |k: &i32| -(*k as f32);
+        for i in items.iter() {
+            t = t.insert_with_priority_source(*i, *i, &mut f).0;
+        }
+
+        //largest key has the lowest priority and is at the root
+        assert_eq!(t.key(), 999);
+    }
+}
+
+#[test]
+fn test_treap_iter() {
+    let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
+
+    let mut t = NodePtr::new();
+
+    assert!(t.first().is_none());
+    assert!(t.last().is_none());
+    assert_eq!(t.iter().count(), 0);
+
+    for i in items.iter() {
+        t = t.insert(*i, *i * 10).0;
+    }
+
+    let mut expected = items.clone();
+    expected.sort();
+
+    assert_eq!(t.first().unwrap().key(), -45);
+    assert_eq!(t.last().unwrap().key(), 77);
+
+    assert_eq!(t.iter().map(|x| x.0).collect::<Vec<_>>(), expected);
+
+    assert_eq!(
+        t.iter().rev().map(|x| x.1).collect::<Vec<_>>(),
+        expected.iter().rev().map(|x| x * 10).collect::<Vec<_>>()
+    );
+
+    //alternate between both ends
+    {
+        let mut it = t.iter();
+        let mut v = vec![];
+        while let Some(x) = it.next() {
+            v.push(x.0);
+            if let Some(y) = it.next_back() {
+                v.push(y.0);
+            }
+        }
+        v.sort();
+        assert_eq!(v, expected);
+    }
+
+    //prefix of the iteration
+    assert_eq!(
+        t.iter().take(3).map(|x| x.0).collect::<Vec<_>>(),
+        vec![-45, -30, -25]
+    );
+}
+
+#[test]
+fn test_treap_range() {
+    let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
+
+    let mut t = NodePtr::new();
+
+    for i in items.iter() {
+        t = t.insert(*i, *i).0;
+    }
+
+    let mut expected = items.clone();
+    expected.sort();
+
+    let filtered = |f: &dyn Fn(i32) -> bool| {
+        expected
+            .iter()
+            .cloned()
+            .filter(|x| f(*x))
+            .collect::<Vec<_>>()
+    };
+
+    let keys = |it: Iter<i32, i32>| it.map(|x| x.0).collect::<Vec<_>>();
+
+    assert_eq!(keys(t.range(-9..12)), filtered(&|x| (-9..12).contains(&x)));
+    assert_eq!(
+        keys(t.range(-9..=12)),
+        filtered(&|x| (-9..=12).contains(&x))
+    );
+    assert_eq!(keys(t.range(-8..13)), filtered(&|x| (-8..13).contains(&x)));
+    assert_eq!(keys(t.range(..7)), filtered(&|x| x < 7));
+    assert_eq!(keys(t.range(7..)), filtered(&|x| x >= 7));
+    assert_eq!(
+        keys(t.range((Bound::Excluded(7), Bound::Excluded(56)))),
+        filtered(&|x| x > 7 && x < 56)
+    );
+    assert_eq!(
+        t.range(-100..100).rev().map(|x| x.0).collect::<Vec<_>>(),
+        expected.iter().rev().cloned().collect::<Vec<_>>()
+    );
+    assert_eq!(keys(t.range(7..7)), vec![]);
+    assert_eq!(keys(t.range(7..=7)), vec![7]);
+    assert_eq!(
+        keys(t.range((Bound::Included(10), Bound::Excluded(8)))),
+        vec![]
+    );
+    assert_eq!(keys(t.range(78..)), vec![]);
+    assert_eq!(keys(t.range(..-45)), vec![]);
+}
+
+#[test]
+fn test_treap_non_copy_keys() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Payload(Vec<u8>);
+
+    let mut t = NodePtr::new();
+    let words = ["pear", "apple", "fig", "banana", "cherry", "date"];
+    for (i, w) in words.iter().enumerate() {
+        t = t.insert(w.to_string(), Payload(vec![i as u8])).0;
+    }
+    assert_eq!(
+        t.iter().map(|x| x.0).collect::<Vec<_>>(),
+        vec!["apple", "banana", "cherry", "date", "fig", "pear"]
+    );
+    match t.search("fig".to_string()) {
+        SearchResult::Exact(x) => assert_eq!(x.val(), Payload(vec![2])),
+        _ => panic!("fig not found"),
+    }
+    assert_eq!(
+        t.range("b".to_string().."d".to_string())
+            .map(|x| x.0)
+            .collect::<Vec<_>>(),
+        vec!["banana", "cherry"]
+    );
+    let t = t.remove_by_key("cherry".to_string());
+    assert_eq!(t.iter().count(), 5);
+}
+
+#[test]
+fn test_treap_float_total_order() {
+    let mut t = NodePtr::new();
+    for k in [3., f32::NAN, -1., f32::INFINITY, 0., -0.].iter() {
+        t = t.insert(OrdF32(*k), 0).0;
+    }
+    let keys = t.iter().map(|x| (x.0).0).collect::<Vec<_>>();
+    assert_eq!(keys.len(), 6);
+    assert!(keys[..5]
+        .windows(2)
+        .all(|w| w[0] < w[1] || (w[0] == 0. && w[1] == 0.)));
+    assert!(keys[5].is_nan());
+    assert!(OrdF64(-0.) < OrdF64(0.));
+    assert_eq!(OrdF64(f64::NAN), OrdF64(f64::NAN));
+}
+
+#[test]
+fn test_treap_from_sorted() {
+    let t = NodePtr::<i32, i32>::from_sorted(vec![]);
+    assert!(t.is_empty());
+
+    let count = 10_000;
+    let items = (0..count).map(|x| (x * 2, x)).collect::<Vec<_>>();
+
+    let t = NodePtr::from_sorted(items.clone());
+    assert_eq!(t.iter().collect::<Vec<_>>(), items);
+    assert!(t.par().0.upgrade().is_none());
+    assert_eq!(t.get_root().key(), t.key());
+    for x in t.query_key_range(i32::MIN, i32::MAX) {
+        if let Some(y) = x.child_l() {
+            assert!(x.prio() <= y.prio());
+        }
+        if let Some(y) = x.child_r() {
+            assert!(x.prio() <= y.prio());
+        }
+    }
+    let (_, d_avg, _) = t.dbg_depth();
+    assert!(d_avg < 5. * ((count as f32).log2() + 1.));
+
+    match t.search(4000) {
+        SearchResult::Exact(x) => assert_eq!(x.val(), 2000),
+        _ => panic!("search failure"),
+    }
+    let t = t.insert(4001, -1).0;
+    assert_eq!(t.range(3999..4003).count(), 3);
+
+    //same priorities give the same tree as repeated insertion
+    let mut t2 = NodePtr::new();
+    let mut prio_src = PrioritySeeded::new(7);
+    for (k, v) in items.iter().take(500) {
+        t2 = t2.insert_with_priority_source(*k, *v, &mut prio_src).0;
+    }
+    let t3 = NodePtr::from_sorted_with_priority_source(
+        items.iter().cloned().take(500),
+        &mut PrioritySeeded::new(7),
+    );
+    assert_eq!(t2.key(), t3.key());
+    assert_eq!(t2.dbg_depth(), t3.dbg_depth());
+    assert_eq!(
+        t2.query_key_range(i32::MIN, i32::MAX)
+            .iter()
+            .map(|x| x.prio())
+            .collect::<Vec<_>>(),
+        t3.query_key_range(i32::MIN, i32::MAX)
+            .iter()
+            .map(|x| x.prio())
+            .collect::<Vec<_>>()
+    );
+
+    let t4 = NodePtr::from_sorted_with_priority(vec![(1, 1, 3.), (2, 2, 1.), (3, 3, 2.)]);
+    assert_eq!(t4.key(), 2);
+    assert_eq!(t4.child_l().unwrap().key(), 1);
+    assert_eq!(t4.child_r().unwrap().key(), 3);
+}
+
+#[test]
+fn test_treap_priority_search() {
+    let mut rng = rand::thread_rng();
+
+    let mut t = NodePtr::new();
+    assert!(t.query_top_priority(0, 10).is_none());
+    assert!(t.query_priority_above(0, 10, 0.).is_empty());
+
+    let count = 500;
+    let mut reference = HashMap::new();
+    for _ in 0..count {
+        let k = rng.gen_range(-1000, 1000);
+        let p = rng.gen_range(-1e3, 1e3);
+        t = t.insert_with_priority(k, k * 10, p).0;
+        reference.insert(k, p);
+    }
+
+    let check = |t: &NodePtr<i32, i32>, reference: &HashMap<i32, f32>| {
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let a = rng.gen_range(-1100, 1100);
+            let b = rng.gen_range(-1100, 1100);
+            let p = rng.gen_range(-1e3, 1e3);
+
+            let mut in_range = reference
+                .iter()
+                .filter(|(k, _)| a <= **k && **k < b)
+                .map(|(k, p)| (*k, *p))
+                .collect::<Vec<_>>();
+            in_range.sort_by_key(|x| x.0);
+
+            let top = in_range
+                .iter()
+                .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap())
+                .map(|x| x.0);
+            assert_eq!(t.query_top_priority(a, b).map(|x| x.key()), top);
+
+            let above = in_range
+                .iter()
+                .filter(|x| x.1 < p)
+                .map(|x| x.0)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                t.query_priority_above(a, b, p)
+                    .iter()
+                    .map(|x| x.key())
+                    .collect::<Vec<_>>(),
+                above
+            );
+        }
+    };
+
+    check(&t, &reference);
+
+    let keys = reference.keys().cloned().collect::<Vec<_>>();
+    for k in keys.iter().take(100) {
+        let p = rng.gen_range(-1e3, 1e3);
+        t = t.set_priority_by_key(*k, p);
+        reference.insert(*k, p);
+    }
+
+    check(&t, &reference);
+
+    //moving a node to the top makes it the root
+    let k = keys[0];
+    t = t.set_priority_by_key(k, -1e4);
+    assert_eq!(t.key(), k);
+    assert_eq!(t.query_top_priority(-2000, 2000).unwrap().key(), k);
+
+    //range-restricted priority queue: pop elements in order of priority
+    let mut popped = vec![];
+    while let Some(x) = t.query_top_priority(-200, 200) {
+        popped.push(x.prio());
+        t = x.remove();
+    }
+    let mut expected = reference
+        .iter()
+        .filter(|(k, _)| -200 <= **k && **k < 200)
+        .map(|(k2, p)| if *k2 == k { -1e4 } else { *p })
+        .collect::<Vec<_>>();
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(popped, expected);
+    assert_eq!(t.iter().count(), reference.len() - expected.len());
+}
+
+#[test]
+fn test_treap_aggregate_range() {
+    use std::collections::BTreeMap;
+
+    let mut rng = rand::thread_rng();
+
+    let check = |t: &NodePtr<i64, i64, AggSum>,
+                 t_min: &NodePtr<i64, i64, AggMin>,
+                 t_max: &NodePtr<i64, i64, AggMax>,
+                 reference: &BTreeMap<i64, i64>| {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let a = rng.gen_range(-1100, 1100);
+            let b = rng.gen_range(-1100, 1100);
+            let vals = reference
+                .iter()
+                .filter(|(k, _)| a <= **k && **k < b)
+                .map(|(_, v)| *v)
+                .collect::<Vec<_>>();
+            let sum = if vals.is_empty() {
+                None
+            } else {
+                Some((vals.iter().sum::<i64>(), vals.len() as i64))
+            };
+            assert_eq!(t.aggregate_range(a, b), sum);
+            assert_eq!(t_min.aggregate_range(a, b), vals.iter().min().cloned());
+            assert_eq!(t_max.aggregate_range(a, b), vals.iter().max().cloned());
+        }
+        let total = if reference.is_empty() {
+            None
+        } else {
+            Some((reference.values().sum::<i64>(), reference.len() as i64))
+        };
+        assert_eq!(t.aggregate(), total);
+        assert_eq!(
+            t.iter().collect::<Vec<_>>(),
+            reference.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        );
+    };
+
+    let mut t = NodePtr::<i64, i64, AggSum>::with_aggregate();
+    let mut t_min = NodePtr::<i64, i64, AggMin>::with_aggregate();
+    let mut t_max = NodePtr::<i64, i64, AggMax>::with_aggregate();
+    let mut reference = BTreeMap::new();
+
+    assert_eq!(t.aggregate_range(0, 10), None);
+
+    for _ in 0..500 {
+        let k = rng.gen_range(-1000, 1000);
+        let v = rng.gen_range(-100, 100);
+        t = t.insert(k, v).0;
+        t_min = t_min.insert(k, v).0;
+        t_max = t_max.insert(k, v).0;
+        reference.insert(k, v);
+    }
+    check(&t, &t_min, &t_max, &reference);
+
+    //range updates interleaved with removals and priority changes
+    for _ in 0..200 {
+        let a = rng.gen_range(-1100, 1100);
+        let b = rng.gen_range(-1100, 1100);
+        let d = rng.gen_range(-50, 50);
+        t.apply_range(a, b, d);
+        t_min.apply_range(a, b, d);
+        t_max.apply_range(a, b, d);
+        for (_, v) in reference.range_mut(a..max(a, b)) {
+            *v += d;
+        }
+
+        let k = rng.gen_range(-1000, 1000);
+        if reference.remove(&k).is_some() {
+            t = t.remove_by_key(k);
+            t_min = t_min.remove_by_key(k);
+            t_max = t_max.remove_by_key(k);
+        }
+
+        let k = rng.gen_range(-1000, 1000);
+        let p = rng.gen_range(0., 1.);
+        t = t.set_priority_by_key(k, p);
+        t_min = t_min.set_priority_by_key(k, p);
+        t_max = t_max.set_priority_by_key(k, p);
+    }
+    check(&t, &t_min, &t_max, &reference);
+
+    //updates on a subtree are reflected in the aggregates of the whole tree
+    if let Some(x) = t.child_l() {
+        let (k_l, k_r) = (x.first().unwrap().key(), x.last().unwrap().key() + 1);
+        x.apply_range(k_l, k_r, 7);
+        t_min.apply_range(k_l, k_r, 7);
+        t_max.apply_range(k_l, k_r, 7);
+        for (_, v) in reference.range_mut(k_l..k_r) {
+            *v += 7;
+        }
+    }
+    check(&t, &t_min, &t_max, &reference);
+
+    //split and merge
+    let ((l, r), m) = t.split_by_key(0);
+    let left = reference.range(..0).map(|(_, v)| *v).collect::<Vec<_>>();
+    if !left.is_empty() {
+        assert_eq!(
+            l.aggregate(),
+            Some((left.iter().sum::<i64>(), left.len() as i64))
+        );
+    }
+    let t2 = match m {
+        Some(x) => l.merge_contiguous(x).merge_contiguous(r),
+        _ => l.merge_contiguous(r),
+    };
+    check(&t2, &t_min, &t_max, &reference);
+
+    //set operations
+    let mut other = NodePtr::<i64, i64, AggSum>::with_aggregate();
+    let mut reference_other = BTreeMap::new();
+    for _ in 0..300 {
+        let k = rng.gen_range(-1000, 1000);
+        let v = rng.gen_range(-100, 100);
+        other = other.insert(k, v).0;
+        reference_other.insert(k, v);
+    }
+    other.apply_range(-500, 500, 3);
+    for (_, v) in reference_other.range_mut(-500..500) {
+        *v += 3;
+    }
+
+    let mut reference_union = reference.clone();
+    for (k, v) in reference_other.iter() {
+        *reference_union.entry(*k).or_insert(0) += *v;
+    }
+    let u = t2.union_with(other, |a, b| a + b);
+    for _ in 0..100 {
+        let a = rng.gen_range(-1100, 1100);
+        let b = rng.gen_range(-1100, 1100);
+        let vals = reference_union
+            .iter()
+            .filter(|(k, _)| a <= **k && **k < b)
+            .map(|(_, v)| *v)
+            .collect::<Vec<_>>();
+        let sum = if vals.is_empty() {
+            None
+        } else {
+            Some((vals.iter().sum::<i64>(), vals.len() as i64))
+        };
+        assert_eq!(u.aggregate_range(a, b), sum);
+    }
+
+    //linear time construction
+    let items = reference.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+    let t3 = NodePtr::<i64, i64, AggSum>::from_sorted_with_aggregate(items.clone());
+    let t3_min = NodePtr::<i64, i64, AggMin>::from_sorted_with_aggregate(items.clone());
+    let t3_max = NodePtr::<i64, i64, AggMax>::from_sorted_with_aggregate(items.clone());
+    check(&t3, &t3_min, &t3_max, &reference);
+
+    //same priorities give the same shape with and without an aggregate
+    let t4 = NodePtr::<i64, i64, AggSum>::from_sorted_with_aggregate_and_priority_source(
+        items.clone(),
+        &mut PrioritySeeded::new(3),
+    );
+    let t4_plain =
+        NodePtr::from_sorted_with_priority_source(items.clone(), &mut PrioritySeeded::new(3));
+    assert_eq!(t4.key(), t4_plain.key());
+    assert_eq!(t4.dbg_depth(), t4_plain.dbg_depth());
+
+    //tags are ignored without an aggregate
+    t4_plain.apply_range(i64::MIN, i64::MAX, ());
+    assert_eq!(t4_plain.iter().collect::<Vec<_>>(), items);
+}
+
+#[test]
+fn test_treap_self_adjusting() {
+    //seeded so the compared depths are the same on every run
+    let mut rng = StdRng::seed_from_u64(11);
+
+    let count = 2000;
+    let mut keys = (0..count).collect::<Vec<i32>>();
+    for i in (1..keys.len()).rev() {
+        keys.swap(i, rng.gen_range(0, i + 1));
+    }
+
+    let mut src_random = PrioritySeeded::new(12);
+    let mut src_count = PriorityAccessCount::seeded(13);
+    let mut src_access_random = PriorityAccessRandom::seeded(14);
+
+    let mut t_random = NodePtr::new();
+    let mut t_count = NodePtr::new();
+    let mut t_access_random = NodePtr::new();
+    for k in keys.iter() {
+        t_random = t_random
+            .insert_with_priority_source(*k, *k, &mut src_random)
+            .0;
+        t_count = t_count
+            .insert_with_priority_source(*k, *k, &mut src_count)
+            .0;
+        t_access_random = t_access_random
+            .insert_with_priority_source(*k, *k, &mut src_access_random)
+            .0;
+    }
+
+    //skewed lookups: a few hot keys take most of the accesses
+    let lookups = (0..20000)
+        .map(|_| keys[(count as f32 * rng.gen_range(0f32, 1f32).powi(6)) as usize])
+        .collect::<Vec<_>>();
+
+    let mut depth_random = 0;
+    let mut depth_count = 0;
+    let mut depth_access_random = 0;
+    for (i, k) in lookups.iter().enumerate() {
+        let (root, r) = t_random.search_adjust(*k, &mut |_: f32| f32::INFINITY);
+        t_random = root;
+        let d_random = match r {
+            SearchResult::Exact(x) => x.depth(),
+            _ => panic!("key not found"),
+        };
+
+        let (root, r) = t_count.search_adjust(*k, &mut src_count);
+        t_count = root;
+        let d_count = match r {
+            SearchResult::Exact(x) => {
+                assert_eq!(x.val(), *k);
+                x.depth()
+            }
+            _ => panic!("key not found"),
+        };
+
+        let (root, r) = t_access_random.search_adjust(*k, &mut src_access_random);
+        t_access_random = root;
+        let d_access_random = match r {
+            SearchResult::Exact(x) => x.depth(),
+            _ => panic!("key not found"),
+        };
+
+        //skip warm up
+        if i >= lookups.len() / 2 {
+            depth_random += d_random;
+            depth_count += d_count;
+            depth_access_random += d_access_random;
+        }
+    }
+
+    let n = (lookups.len() / 2) as f32;
+    println!(
+        "avg access depth: random: {}, count: {}, random on access: {}",
+        depth_random as f32 / n,
+        depth_count as f32 / n,
+        depth_access_random as f32 / n
+    );
+    println!(
+        "dbg_depth (min, max, avg): random: {:?}, count: {:?}, random on access: {:?}",
+        t_random.dbg_depth(),
+        t_count.dbg_depth(),
+        t_access_random.dbg_depth()
+    );
+
+    assert!(depth_count < depth_random);
+    assert!(depth_access_random < depth_random);
+
+    //access counts are exact
+    let hot = keys[0];
+    match t_count.search(hot) {
+        SearchResult::Exact(x) => assert_eq!(
+            PriorityAccessCount::count(x.prio()) as usize,
+            lookups.iter().filter(|k| **k == hot).count()
+        ),
+        _ => panic!("key not found"),
+    }
+
+    for t in [&t_random, &t_count, &t_access_random].iter() {
+        assert_eq!(
+            t.iter().map(|x| x.0).collect::<Vec<_>>(),
+            (0..count).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn test_treap_multiset() {
+    use std::collections::BTreeMap;
+
+    let mut rng = rand::thread_rng();
+
+    let mut a = Multiset::new();
+    let mut b = Multiset::new();
+    let mut reference_a = BTreeMap::new();
+    let mut reference_b = BTreeMap::new();
+
+    assert!(a.is_empty());
+    assert_eq!(a.total_len(), 0);
+    assert!(!a.remove_one(5));
+
+    for _ in 0..2000 {
+        let k = rng.gen_range(-100, 100);
+        a.insert_one(k);
+        *reference_a.entry(k).or_insert(0) += 1;
+
+        let k = rng.gen_range(-100, 100);
+        b.insert_one(k);
+        *reference_b.entry(k).or_insert(0) += 1;
+    }
+    for _ in 0..1500 {
+        let k = rng.gen_range(-100, 100);
+        let removed = match reference_a.get_mut(&k) {
+            Some(c) => {
+                *c -= 1;
+                true
+            }
+            _ => false,
+        };
+        if reference_a.get(&k) == Some(&0) {
+            reference_a.remove(&k);
+        }
+        assert_eq!(a.remove_one(k), removed);
+    }
+
+    for k in -110..110 {
+        assert_eq!(a.count(k), *reference_a.get(&k).unwrap_or(&0));
+    }
+    assert_eq!(a.total_len(), reference_a.values().sum::<usize>());
+    assert_eq!(a.len(), reference_a.len());
+    assert_eq!(
+        a.count_range(-50, 50),
+        reference_a.range(-50..50).map(|(_, c)| *c).sum::<usize>()
+    );
+    assert_eq!(
+        a.to_vec(),
+        reference_a
+            .iter()
+            .map(|(k, c)| (*k, *c))
+            .collect::<Vec<_>>()
+    );
+
+    //count-aware set operations
+    let mut reference_union = reference_a.clone();
+    for (k, c) in reference_b.iter() {
+        *reference_union.entry(*k).or_insert(0) += *c;
+    }
+    let reference_intersect = reference_a
+        .iter()
+        .filter_map(|(k, c)| reference_b.get(k).map(|c2| (*k, min(*c, *c2))))
+        .collect::<Vec<_>>();
+
+    let mut a2 = Multiset::new();
+    let mut b2 = Multiset::new();
+    for (k, c) in reference_a.iter() {
+        for _ in 0..*c {
+            a2.insert_one(*k);
+        }
+    }
+    for (k, c) in reference_b.iter() {
+        for _ in 0..*c {
+            b2.insert_one(*k);
+        }
+    }
+
+    let u = a.union(b);
+    assert_eq!(
+        u.to_vec(),
+        reference_union
+            .iter()
+            .map(|(k, c)| (*k, *c))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(u.total_len(), reference_union.values().sum::<usize>());
+
+    let i = a2.intersect(b2);
+    assert_eq!(i.to_vec(), reference_intersect);
+    assert_eq!(
+        i.total_len(),
+        reference_intersect.iter().map(|(_, c)| *c).sum::<usize>()
+    );
+}
+
+#[test]
+fn test_treap_finger_search() {
+    let mut rng = rand::thread_rng();
+
+    let mut t = NodePtr::new();
+    match t.search_from(5) {
+        SearchResult::Empty => {}
+        _ => panic!("expected empty"),
+    }
+    assert_eq!(t.range_from(0..10).count(), 0);
+
+    for _ in 0..2000 {
+        let k = rng.gen_range(-5000, 5000);
+        t = t.insert(k, k * 2).0;
+    }
+    let nodes = t.query_key_range(i32::MIN, i32::MAX);
+    let keys = nodes.iter().map(|x| x.key()).collect::<Vec<_>>();
+
+    for _ in 0..2000 {
+        let finger = &nodes[rng.gen_range(0, nodes.len())];
+        let k = rng.gen_range(-5100, 5100);
+        match (finger.search_from(k), t.search(k)) {
+            (SearchResult::Exact(a), SearchResult::Exact(b)) => {
+                assert!(Rc::ptr_eq(&a.0, &b.0));
+                assert_eq!(a.val(), k * 2);
+            }
+            (SearchResult::Nearest(a), SearchResult::Nearest(b)) => {
+                assert!(Rc::ptr_eq(&a.0, &b.0));
+            }
+            _ => panic!("search results differ"),
+        }
+
+        let a = rng.gen_range(-5100, 5100);
+        let b = rng.gen_range(-5100, 5100);
+        let expected = keys
+            .iter()
+            .cloned()
+            .filter(|x| a <= *x && *x < b)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            finger
+                .query_key_range_from(a, b)
+                .iter()
+                .map(|x| x.key())
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(
+            finger
+                .range_from((Bound::Excluded(a), Bound::Included(b)))
+                .map(|x| x.0)
+                .collect::<Vec<_>>(),
+            keys.iter()
+                .cloned()
+                .filter(|x| a < *x && *x <= b)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            finger
+                .range_from(a..)
+                .rev()
+                .map(|x| x.0)
+                .collect::<Vec<_>>(),
+            keys.iter()
+                .cloned()
+                .filter(|x| a <= *x)
+                .rev()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    //lookups of neighbouring keys climb only a few levels on average
+    let mut climbed = 0;
+    for w in nodes.windows(2) {
+        let top = w[0].climb(&w[1].key());
+        climbed += w[0].depth() - top.depth();
+    }
+    let avg = climbed as f32 / (nodes.len() - 1) as f32;
+    println!("avg levels climbed for rank distance 1: {}", avg);
+    assert!(avg < 4.);
+}
+
+#[test]
+fn test_treap_diff() {
+    use std::collections::BTreeMap;
+
+    let mut rng = rand::thread_rng();
+
+    let mut a = NodePtr::new();
+    let mut b = NodePtr::new();
+    assert_eq!(a.diff(&b).count(), 0);
+
+    let mut reference_a = BTreeMap::new();
+    let mut reference_b = BTreeMap::new();
+    for _ in 0..1000 {
+        let k = rng.gen_range(-1000, 1000);
+        let v = rng.gen_range(0, 3);
+        a = a.insert(k, v).0;
+        reference_a.insert(k, v);
+
+        let k = rng.gen_range(-1000, 1000);
+        let v = rng.gen_range(0, 3);
+        b = b.insert(k, v).0;
+        reference_b.insert(k, v);
+    }
+
+    let mut expected = vec![];
+    let mut keys = reference_a
+        .keys()
+        .chain(reference_b.keys())
+        .cloned()
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    for k in keys {
+        match (reference_a.get(&k), reference_b.get(&k)) {
+            (Some(x), None) => expected.push(Diff::Removed(k, *x)),
+            (None, Some(y)) => expected.push(Diff::Added(k, *y)),
+            (Some(x), Some(y)) if x != y => expected.push(Diff::Changed(k, *x, *y)),
+            _ => {}
+        }
+    }
+
+    let d = a.diff(&b).collect::<Vec<_>>();
+    assert_eq!(d, expected);
+    assert_eq!(b.diff(&b).count(), 0);
+
+    //applying the diff turns a into a copy of b
+    let a2 = a.apply(d);
+    assert_eq!(a2.diff(&b).count(), 0);
+    assert_eq!(
+        a2.iter().collect::<Vec<_>>(),
+        reference_b
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect::<Vec<_>>()
+    );
+
+    //aggregates are kept up to date
+    let mut c = NodePtr::<i32, i64, AggSum>::with_aggregate();
+    let mut e = NodePtr::<i32, i64, AggSum>::with_aggregate();
+    for (k, v) in reference_a.iter() {
+        c = c.insert(*k, *v as i64).0;
+    }
+    for (k, v) in reference_b.iter() {
+        e = e.insert(*k, *v as i64).0;
+    }
+    let c = c.apply(c.diff(&e).collect::<Vec<_>>());
+    assert_eq!(c.aggregate(), e.aggregate());
+}
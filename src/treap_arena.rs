@@ -0,0 +1,2071 @@
+//! arena-based treap: nodes of all instances live in shared vectors and are addressed by index,
+//! removed slots are recycled through a freelist;
+//! set operations are based from:
+//! Fast Set Operations Using Treaps: http://www.cs.cmu.edu/afs/cs.cmu.edu/project/scandal/public/papers/treaps-spaa98.html
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::ops::{Bound, RangeBounds};
+
+use treap::{Priority, PriorityRandom};
+
+#[cfg(test)]
+extern crate rand;
+#[cfg(test)]
+use self::rand::Rng;
+
+#[cfg(test)]
+extern crate chrono;
+#[cfg(test)]
+use self::chrono::prelude::*;
+
+#[cfg(test)]
+use treap::OrdF32;
+
+#[derive(Clone, Debug)]
+pub struct Treap<K, T>
+where
+    K: Ord + Clone + Debug,
+    T: Clone + Debug,
+{
+    /// keys and values are cleared when a slot is freed
+    pub keys: Vec<Option<K>>,
+    pub priorities: Vec<f32>,
+    pub vals: Vec<Option<T>>,
+    pub link_child: Vec<(Option<usize>, Option<usize>)>,
+    pub link_parent: Vec<usize>,
+    pub freelist: Vec<usize>,
+    pub instances: HashMap<usize, Option<usize>>,
+}
+
+pub enum SearchResult<K, T>
+where
+    K: Ord + Clone + Debug,
+    T: Clone + Debug,
+{
+    Exact((usize, K, T)),
+    Nearest((usize, K, T)),
+    Empty,
+}
+
+pub enum ChildBranch {
+    Left,
+    Right,
+    NotApplicable,
+}
+
+/// lazy in-order iterator over node indices of a treap instance
+pub struct Iter<'a, K, T>
+where
+    K: Ord + Clone + Debug,
+    T: Clone + Debug,
+{
+    t: &'a Treap<K, T>,
+    front: Option<usize>,
+    back: Option<usize>,
+}
+
+impl<'a, K, T> Iterator for Iter<'a, K, T>
+where
+    K: Ord + Clone + Debug,
+    T: Clone + Debug,
+{
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let cur = self.front.take()?;
+        if self.back == Some(cur) {
+            self.back = None;
+        } else {
+            self.front = self.t.successor(cur);
+        }
+        Some(cur)
+    }
+}
+
+impl<'a, K, T> DoubleEndedIterator for Iter<'a, K, T>
+where
+    K: Ord + Clone + Debug,
+    T: Clone + Debug,
+{
+    fn next_back(&mut self) -> Option<usize> {
+        let cur = self.back.take()?;
+        if self.front == Some(cur) {
+            self.front = None;
+        } else {
+            self.back = self.t.predecessor(cur);
+        }
+        Some(cur)
+    }
+}
+
+impl<K, T> Default for Treap<K, T>
+where
+    K: Ord + Clone + Debug,
+    T: Clone + Debug,
+{
+    fn default() -> Self {
+        Treap {
+            keys: vec![],
+            priorities: vec![],
+            vals: vec![],
+            link_child: vec![],
+            link_parent: vec![],
+            freelist: vec![],
+            instances: HashMap::new(),
+        }
+    }
+}
+
+impl<K, T> Treap<K, T>
+where
+    K: Ord + Clone + Debug,
+    T: Clone + Debug,
+{
+    ///helper function
+    fn new_slot(&mut self, k: K, p: f32, val: T) -> usize {
+        match self.freelist.pop() {
+            Some(idx) => {
+                self.keys[idx] = Some(k);
+                self.priorities[idx] = p;
+                self.vals[idx] = Some(val);
+                self.link_child[idx] = (None, None);
+                self.link_parent[idx] = idx;
+                idx
+            }
+            _ => {
+                let l = self.keys.len();
+
+                self.keys.push(Some(k));
+                self.priorities.push(p);
+                self.vals.push(Some(val));
+                self.link_child.push((None, None));
+                self.link_parent.push(l);
+
+                l
+            }
+        }
+    }
+
+    ///helper function
+    fn free_slot(&mut self, idx: usize) {
+        self.keys[idx] = None;
+        self.vals[idx] = None;
+        self.link_child[idx] = (None, None);
+        self.link_parent[idx] = idx;
+        self.freelist.push(idx);
+    }
+
+    ///helper function: recycles all slots of a subtree
+    fn free_subtree(&mut self, n: Option<usize>) {
+        let mut q = n.into_iter().collect::<Vec<_>>();
+        while let Some(x) = q.pop() {
+            q.extend(self.link_child[x].0);
+            q.extend(self.link_child[x].1);
+            self.free_slot(x);
+        }
+    }
+
+    ///helper function
+    pub fn key(&self, a: usize) -> K {
+        self.key_ref(a).clone()
+    }
+
+    ///helper function
+    pub fn val(&self, a: usize) -> T {
+        self.val_ref(a).clone()
+    }
+
+    ///helper function
+    fn key_ref(&self, a: usize) -> &K {
+        self.keys[a].as_ref().expect("key of free slot")
+    }
+
+    ///helper function
+    fn val_ref(&self, a: usize) -> &T {
+        self.vals[a].as_ref().expect("value of free slot")
+    }
+    pub fn prio(&self, a: usize) -> f32 {
+        assert!(a < self.priorities.len());
+        self.priorities[a]
+    }
+
+    /// true if node a belongs above node b: lower prio first, then lower key
+    pub fn ranks_above(&self, a: usize, b: usize) -> bool {
+        let (pa, pb) = (self.prio(a), self.prio(b));
+        pa < pb || (pa == pb && *self.key_ref(a) < *self.key_ref(b))
+    }
+
+    pub fn init() -> Self {
+        Treap::default()
+    }
+
+    pub fn new_instance(&mut self) -> usize {
+        let l = self.instances.len();
+        self.instances.insert(l, None);
+        l
+    }
+
+    ///helper function
+    fn root(&self, instance: usize) -> Option<usize> {
+        *self
+            .instances
+            .get(&instance)
+            .expect("instance non-existent")
+    }
+
+    ///helper function: updates root of an instance
+    fn set_root(&mut self, instance: usize, r: Option<usize>) {
+        if let Some(x) = r {
+            self.link_parent[x] = x;
+        }
+        self.instances.insert(instance, r);
+    }
+
+    pub fn search(&self, instance: usize, k: K) -> SearchResult<K, T> {
+        let r: usize = match self.instances.get(&instance) {
+            Some(Some(x)) => *x,
+            _ => return SearchResult::Empty,
+        };
+        let mut n = r;
+        loop {
+            assert!(n < self.keys.len());
+            if k < *self.key_ref(n) {
+                match self.link_child[n].0 {
+                    Some(x) => n = x,
+                    _ => break SearchResult::Nearest((n, self.key(n), self.val(n))),
+                }
+            } else if k > *self.key_ref(n) {
+                match self.link_child[n].1 {
+                    Some(x) => n = x,
+                    _ => break SearchResult::Nearest((n, self.key(n), self.val(n))),
+                }
+            } else {
+                break SearchResult::Exact((n, k, self.val(n)));
+            }
+        }
+    }
+
+    ///return position of an item if it has the same key, priority is updated for an existing item
+    pub fn insert_with_priority(&mut self, instance: usize, k: K, p: f32, val: T) -> (bool, usize) {
+        match self.search(instance, k.clone()) {
+            SearchResult::Empty => {
+                let idx = self.new_slot(k, p, val);
+                self.set_root(instance, Some(idx));
+
+                (false, idx)
+            }
+            SearchResult::Exact((n_pos, _, _)) => {
+                //item with key already exists
+                self.vals[n_pos] = Some(val);
+                self.priorities[n_pos] = p;
+                self.fixup_priority(instance, n_pos);
+
+                (true, n_pos)
+            }
+            SearchResult::Nearest((n_pos, n_key, _)) => {
+                let go_left = k < n_key;
+
+                let idx = self.new_slot(k, p, val);
+
+                self.link_parent[idx] = n_pos;
+
+                if go_left {
+                    self.link_child[n_pos].0 = Some(idx);
+                } else {
+                    self.link_child[n_pos].1 = Some(idx);
+                }
+
+                self.fixup_priority(instance, idx);
+
+                (false, idx)
+            }
+        }
+    }
+
+    pub fn insert(&mut self, instance: usize, k: K, val: T) -> (bool, usize) {
+        self.insert_with_priority_source(instance, k, val, &mut PriorityRandom)
+    }
+
+    ///inserts an item with priority drawn from prio_src
+    pub fn insert_with_priority_source<P>(
+        &mut self,
+        instance: usize,
+        k: K,
+        val: T,
+        prio_src: &mut P,
+    ) -> (bool, usize)
+    where
+        P: Priority<K>,
+    {
+        let priority = prio_src.priority(&k);
+        self.insert_with_priority(instance, k, priority, val)
+    }
+
+    /// builds a new instance in O(n) from items with strictly increasing keys
+    /// and returns its handle
+    pub fn from_sorted<I>(&mut self, items: I) -> usize
+    where
+        I: IntoIterator<Item = (K, T)>,
+    {
+        self.from_sorted_with_priority_source(items, &mut PriorityRandom)
+    }
+
+    /// builds a new instance in O(n) from items with strictly increasing keys,
+    /// priorities are drawn from prio_src in order of the items
+    pub fn from_sorted_with_priority_source<I, P>(&mut self, items: I, prio_src: &mut P) -> usize
+    where
+        I: IntoIterator<Item = (K, T)>,
+        P: Priority<K>,
+    {
+        self.from_sorted_with_priority(items.into_iter().map(|(k, val)| {
+            let p = prio_src.priority(&k);
+            (k, p, val)
+        }))
+    }
+
+    /// builds a new instance in O(n) from (key, priority, value) items with strictly increasing keys
+    /// by maintaining the right spine of the tree in a stack
+    pub fn from_sorted_with_priority<I>(&mut self, items: I) -> usize
+    where
+        I: IntoIterator<Item = (K, f32, T)>,
+    {
+        let mut spine: Vec<usize> = vec![];
+        for (k, p, val) in items {
+            if let Some(x) = spine.last() {
+                assert!(*self.key_ref(*x) < k, "keys not strictly increasing");
+            }
+            let idx = self.new_slot(k, p, val);
+            let mut last = None;
+            while let Some(x) = spine.pop() {
+                if self.prio(x) > p {
+                    last = Some(x);
+                } else {
+                    spine.push(x);
+                    break;
+                }
+            }
+            self.link_left(idx, last);
+            if let Some(x) = spine.last() {
+                self.link_right(*x, Some(idx));
+            }
+            spine.push(idx);
+        }
+        let instance = self.new_instance();
+        self.set_root(instance, spine.first().cloned());
+        instance
+    }
+
+    fn child_branch(&self, n: usize, parent: usize) -> ChildBranch {
+        match self.link_child[parent].0 {
+            Some(x) if x == n => return ChildBranch::Left,
+            _ => {}
+        }
+        match self.link_child[parent].1 {
+            Some(x) if x == n => return ChildBranch::Right,
+            _ => {}
+        }
+        ChildBranch::NotApplicable
+    }
+
+    fn fixup_priority(&mut self, instance: usize, n: usize) {
+        //fix priority by rotating up the tree
+
+        let mut r: usize = self
+            .instances
+            .get(&instance)
+            .unwrap()
+            .expect("root non-existent");
+
+        let mut par = self.link_parent[n];
+
+        while self.ranks_above(n, par) && r != n {
+            match self.child_branch(n, par) {
+                ChildBranch::Left => {
+                    self.rot_left(instance, n, par);
+                }
+                ChildBranch::Right => {
+                    self.rot_right(instance, n, par);
+                }
+                _ => {
+                    panic!("child link error");
+                }
+            }
+            par = self.link_parent[n];
+            r = self
+                .instances
+                .get(&instance)
+                .unwrap()
+                .expect("root non-existent");
+        }
+    }
+
+    fn link_left(&mut self, parent: usize, child: Option<usize>) {
+        match child {
+            Some(x) => {
+                self.link_parent[x] = parent;
+                self.link_child[parent].0 = Some(x);
+            }
+            _ => {
+                self.link_child[parent].0 = None;
+            }
+        }
+    }
+
+    fn link_right(&mut self, parent: usize, child: Option<usize>) {
+        match child {
+            Some(x) => {
+                self.link_parent[x] = parent;
+                self.link_child[parent].1 = Some(x);
+            }
+            _ => {
+                self.link_child[parent].1 = None;
+            }
+        }
+    }
+
+    fn rot_left(&mut self, instance: usize, n: usize, parent: usize) {
+        // before:
+        //          pp
+        //          |
+        //          p
+        //         / \
+        //        n   c
+        //       / \
+        //      a   b
+        //
+        // after:
+        //          pp
+        //          |
+        //          n
+        //         / \
+        //        a   p
+        //           / \
+        //          b   c
+        //
+
+        let parent_is_root = match self.root(instance) {
+            Some(x) if x == n => {
+                return;
+            }
+            Some(x) => x == parent,
+            _ => {
+                panic!("root does not exist");
+            }
+        };
+
+        if !parent_is_root {
+            let pp = self.link_parent[parent];
+
+            match self.child_branch(parent, pp) {
+                ChildBranch::Left => {
+                    self.link_left(pp, Some(n));
+                }
+                ChildBranch::Right => {
+                    self.link_right(pp, Some(n));
+                }
+                _ => {
+                    panic!();
+                }
+            }
+        } else {
+            //update to new root
+            self.instances.insert(instance, Some(n));
+
+            self.link_parent[n] = n;
+        }
+
+        self.link_left(parent, self.link_child[n].1);
+
+        self.link_right(n, Some(parent));
+    }
+
+    fn rot_right(&mut self, instance: usize, n: usize, parent: usize) {
+        // before:
+        //          pp
+        //          |
+        //          p
+        //         / \
+        //        c   n
+        //           / \
+        //          a   b
+        //
+        // after:
+        //          pp
+        //          |
+        //          n
+        //         / \
+        //        p   b
+        //       / \
+        //      c   a
+        //
+
+        let parent_is_root = match self.root(instance) {
+            Some(x) if x == n => {
+                return;
+            }
+            Some(x) => x == parent,
+            _ => {
+                panic!("root does not exist");
+            }
+        };
+
+        if !parent_is_root {
+            let pp = self.link_parent[parent];
+
+            match self.child_branch(parent, pp) {
+                ChildBranch::Left => {
+                    self.link_left(pp, Some(n));
+                }
+                ChildBranch::Right => {
+                    self.link_right(pp, Some(n));
+                }
+                _ => {
+                    panic!();
+                }
+            }
+        } else {
+            //update to new root
+            self.instances.insert(instance, Some(n));
+
+            self.link_parent[n] = n;
+        }
+
+        self.link_right(parent, self.link_child[n].0);
+
+        self.link_left(n, Some(parent));
+    }
+
+    pub fn successor(&self, idx: usize) -> Option<usize> {
+        if let Some(x) = self.link_child[idx].1 {
+            let mut cur = x;
+            while let Some(y) = self.link_child[cur].0 {
+                cur = y;
+            }
+            Some(cur)
+        } else {
+            let mut cur = idx;
+            let mut p = self.link_parent[cur];
+
+            loop {
+                match self.child_branch(cur, p) {
+                    ChildBranch::Left => return Some(p),
+                    ChildBranch::Right => {
+                        cur = p;
+                        p = self.link_parent[p];
+                    }
+                    _ => return None,
+                }
+            }
+        }
+    }
+
+    pub fn predecessor(&self, idx: usize) -> Option<usize> {
+        if let Some(x) = self.link_child[idx].0 {
+            let mut cur = x;
+            while let Some(y) = self.link_child[cur].1 {
+                cur = y;
+            }
+            Some(cur)
+        } else {
+            let mut cur = idx;
+            let mut p = self.link_parent[cur];
+
+            loop {
+                match self.child_branch(cur, p) {
+                    ChildBranch::Right => return Some(p),
+                    ChildBranch::Left => {
+                        cur = p;
+                        p = self.link_parent[p];
+                    }
+                    _ => return None,
+                }
+            }
+        }
+    }
+
+    pub fn first(&self, instance: usize) -> Option<usize> {
+        self.bound_lower(instance, Bound::Unbounded)
+    }
+
+    pub fn last(&self, instance: usize) -> Option<usize> {
+        self.bound_upper(instance, Bound::Unbounded)
+    }
+
+    ///helper function: returns the index with the lowest key within the lower bound
+    fn bound_lower(&self, instance: usize, b: Bound<&K>) -> Option<usize> {
+        let mut ret = None;
+        let mut cur = self.root(instance);
+        while let Some(x) = cur {
+            let within = match b {
+                Bound::Included(k) => *k <= *self.key_ref(x),
+                Bound::Excluded(k) => *k < *self.key_ref(x),
+                Bound::Unbounded => true,
+            };
+            if within {
+                cur = self.link_child[x].0;
+                ret = Some(x);
+            } else {
+                cur = self.link_child[x].1;
+            }
+        }
+        ret
+    }
+
+    ///helper function: returns the index with the highest key within the upper bound
+    fn bound_upper(&self, instance: usize, b: Bound<&K>) -> Option<usize> {
+        let mut ret = None;
+        let mut cur = self.root(instance);
+        while let Some(x) = cur {
+            let within = match b {
+                Bound::Included(k) => *self.key_ref(x) <= *k,
+                Bound::Excluded(k) => *self.key_ref(x) < *k,
+                Bound::Unbounded => true,
+            };
+            if within {
+                cur = self.link_child[x].1;
+                ret = Some(x);
+            } else {
+                cur = self.link_child[x].0;
+            }
+        }
+        ret
+    }
+
+    /// returns a lazy in-order iterator over indices of items in the instance
+    pub fn iter(&self, instance: usize) -> Iter<'_, K, T> {
+        self.range(instance, ..)
+    }
+
+    /// returns a lazy in-order iterator over indices of items with keys in range
+    pub fn range<R>(&self, instance: usize, range: R) -> Iter<'_, K, T>
+    where
+        R: RangeBounds<K>,
+    {
+        let front = self.bound_lower(instance, range.start_bound());
+        let back = self.bound_upper(instance, range.end_bound());
+        match (front, back) {
+            (Some(a), Some(b)) if *self.key_ref(a) <= *self.key_ref(b) => Iter {
+                t: self,
+                front: Some(a),
+                back: Some(b),
+            },
+            _ => Iter {
+                t: self,
+                front: None,
+                back: None,
+            },
+        }
+    }
+
+    /// get indices of items with key in [k_start,k_end)
+    pub fn query_range(&self, instance: usize, k_start: K, k_end: K) -> Vec<usize> {
+        self.range(instance, k_start..k_end).collect()
+    }
+
+    pub fn remove_index(&mut self, instance: usize, idx: usize) {
+        loop {
+            //rotate the child of higher priority up until idx is a leaf
+            let rot_index = match self.link_child[idx] {
+                (Some(l), Some(r)) => {
+                    if self.ranks_above(l, r) {
+                        l
+                    } else {
+                        r
+                    }
+                }
+                (Some(l), None) => l,
+                (None, Some(r)) => r,
+                _ => {
+                    break;
+                }
+            };
+            match self.child_branch(rot_index, idx) {
+                ChildBranch::Left => {
+                    self.rot_left(instance, rot_index, idx);
+                }
+                ChildBranch::Right => {
+                    self.rot_right(instance, rot_index, idx);
+                }
+                _ => {
+                    panic!();
+                }
+            }
+        }
+
+        let p = self.link_parent[idx];
+        if p != idx {
+            match self.child_branch(idx, p) {
+                ChildBranch::Left => {
+                    self.link_child[p].0 = None;
+                }
+                ChildBranch::Right => {
+                    self.link_child[p].1 = None;
+                }
+                _ => {
+                    panic!();
+                }
+            }
+        }
+
+        if self.root(instance) == Some(idx) {
+            self.instances.insert(instance, None);
+        }
+
+        self.free_slot(idx);
+    }
+
+    /// removes items with key in range of [k_start, k_end)
+    pub fn remove_key_range(&mut self, instance: usize, k_start: K, k_end: K) {
+        self.query_range(instance, k_start, k_end)
+            .iter()
+            .for_each(|x| self.remove_index(instance, *x));
+    }
+
+    ///helper function: splits subtree into a:[ x | x.key < k ], b:[ x | x.key >= k ],
+    ///or a:[ x | x.key <= k ], b:[ x | x.key > k ] if inclusive
+    fn split_nodes(
+        &mut self,
+        n: Option<usize>,
+        k: &K,
+        inclusive: bool,
+    ) -> (Option<usize>, Option<usize>) {
+        match n {
+            Some(x) => {
+                let go_left = if inclusive {
+                    *self.key_ref(x) <= *k
+                } else {
+                    *self.key_ref(x) < *k
+                };
+                if go_left {
+                    let (a, b) = self.split_nodes(self.link_child[x].1, k, inclusive);
+                    self.link_right(x, a);
+                    (Some(x), b)
+                } else {
+                    let (a, b) = self.split_nodes(self.link_child[x].0, k, inclusive);
+                    self.link_left(x, b);
+                    (a, Some(x))
+                }
+            }
+            _ => (None, None),
+        }
+    }
+
+    ///helper function: splits subtree into ( [x| x.key<k], node with key k, [x| x.key>k] )
+    fn split3(&mut self, n: Option<usize>, k: &K) -> (Option<usize>, Option<usize>, Option<usize>) {
+        let (lt, ge) = self.split_nodes(n, k, false);
+        let (eq, gt) = self.split_nodes(ge, k, true);
+        (lt, eq, gt)
+    }
+
+    ///helper function: joins subtrees where a.keys < b.keys
+    fn merge_nodes(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match (a, b) {
+            (Some(x), Some(y)) => {
+                if self.prio(x) <= self.prio(y) {
+                    let r = self.merge_nodes(self.link_child[x].1, Some(y));
+                    self.link_right(x, r);
+                    Some(x)
+                } else {
+                    let l = self.merge_nodes(Some(x), self.link_child[y].0);
+                    self.link_left(y, l);
+                    Some(y)
+                }
+            }
+            (None, x) | (x, None) => x,
+        }
+    }
+
+    /// split given treap instance into two instances: a:[ x | x.key < k ], b:[ x | x.key >= k ]
+    /// returns instance handles to split treaps (a,b)
+    pub fn split(&mut self, instance: usize, k: K) -> (usize, usize) {
+        let r = self.root(instance);
+        let (a, b) = self.split_nodes(r, &k, false);
+        self.set_root(instance, a);
+        let new_inst = self.new_instance();
+        self.set_root(new_inst, b);
+        (instance, new_inst)
+    }
+
+    /// merges 2 trees (a.keys < b.keys) and returns handle to a combined tree, b is left empty
+    pub fn merge(&mut self, inst_a: usize, inst_b: usize) -> usize {
+        let r = self.merge_nodes(self.root(inst_a), self.root(inst_b));
+        self.set_root(inst_b, None);
+        self.set_root(inst_a, r);
+        inst_a
+    }
+
+    ///helper function: f is called with values of a and b respectively for common keys
+    fn union_nodes<F>(&mut self, a: Option<usize>, b: Option<usize>, f: &mut F) -> Option<usize>
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        match (a, b) {
+            (Some(x), Some(y)) => {
+                if self.prio(x) <= self.prio(y) {
+                    let k = self.key(x);
+                    let (lt, eq, gt) = self.split3(Some(y), &k);
+                    if let Some(e) = eq {
+                        let v = f(self.val_ref(x), self.val_ref(e));
+                        self.vals[x] = Some(v);
+                        self.free_slot(e);
+                    }
+                    let l = self.union_nodes(self.link_child[x].0, lt, f);
+                    let r = self.union_nodes(self.link_child[x].1, gt, f);
+                    self.link_left(x, l);
+                    self.link_right(x, r);
+                    Some(x)
+                } else {
+                    let k = self.key(y);
+                    let (lt, eq, gt) = self.split3(Some(x), &k);
+                    if let Some(e) = eq {
+                        let v = f(self.val_ref(e), self.val_ref(y));
+                        self.vals[y] = Some(v);
+                        self.free_slot(e);
+                    }
+                    let l = self.union_nodes(lt, self.link_child[y].0, f);
+                    let r = self.union_nodes(gt, self.link_child[y].1, f);
+                    self.link_left(y, l);
+                    self.link_right(y, r);
+                    Some(y)
+                }
+            }
+            (None, x) | (x, None) => x,
+        }
+    }
+
+    ///helper function: f is called with values of a and b respectively for common keys
+    fn intersect_nodes<F>(&mut self, a: Option<usize>, b: Option<usize>, f: &mut F) -> Option<usize>
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        match (a, b) {
+            (Some(x), Some(y)) => {
+                let (root, root_is_a) = if self.prio(x) <= self.prio(y) {
+                    (x, true)
+                } else {
+                    (y, false)
+                };
+                let other = if root_is_a { y } else { x };
+                let k = self.key(root);
+                let (lt, eq, gt) = self.split3(Some(other), &k);
+                let (root_l, root_r) = self.link_child[root];
+                let (l, r) = if root_is_a {
+                    (
+                        self.intersect_nodes(root_l, lt, f),
+                        self.intersect_nodes(root_r, gt, f),
+                    )
+                } else {
+                    (
+                        self.intersect_nodes(lt, root_l, f),
+                        self.intersect_nodes(gt, root_r, f),
+                    )
+                };
+                match eq {
+                    Some(e) => {
+                        let v = if root_is_a {
+                            f(self.val_ref(root), self.val_ref(e))
+                        } else {
+                            f(self.val_ref(e), self.val_ref(root))
+                        };
+                        self.vals[root] = Some(v);
+                        self.free_slot(e);
+                        self.link_left(root, l);
+                        self.link_right(root, r);
+                        Some(root)
+                    }
+                    _ => {
+                        self.free_slot(root);
+                        self.merge_nodes(l, r)
+                    }
+                }
+            }
+            (None, x) | (x, None) => {
+                self.free_subtree(x);
+                None
+            }
+        }
+    }
+
+    /// union of 2 instances stored into instance a, b is left empty;
+    /// values of a are kept for common keys
+    pub fn union(&mut self, inst_a: usize, inst_b: usize) -> usize {
+        self.union_with(inst_a, inst_b, |a, _| a.clone())
+    }
+
+    /// union of 2 instances stored into instance a, b is left empty;
+    /// values for common keys are combined with f(value of a, value of b)
+    pub fn union_with<F>(&mut self, inst_a: usize, inst_b: usize, mut f: F) -> usize
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        assert!(inst_a != inst_b);
+        let r = self.union_nodes(self.root(inst_a), self.root(inst_b), &mut f);
+        self.set_root(inst_b, None);
+        self.set_root(inst_a, r);
+        inst_a
+    }
+
+    /// intersection of 2 instances stored into instance a, b is left empty;
+    /// values of a are kept for common keys
+    pub fn intersect(&mut self, inst_a: usize, inst_b: usize) -> usize {
+        self.intersect_with(inst_a, inst_b, |a, _| a.clone())
+    }
+
+    /// intersection of 2 instances stored into instance a, b is left empty;
+    /// values for common keys are combined with f(value of a, value of b)
+    pub fn intersect_with<F>(&mut self, inst_a: usize, inst_b: usize, mut f: F) -> usize
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        assert!(inst_a != inst_b);
+        let r = self.intersect_nodes(self.root(inst_a), self.root(inst_b), &mut f);
+        self.set_root(inst_b, None);
+        self.set_root(inst_a, r);
+        inst_a
+    }
+
+    pub fn dbg_depth(&self, instance: usize) -> f32 {
+        let r: usize = match self.instances.get(&instance) {
+            Some(Some(x)) => *x,
+            _ => return 0.,
+        };
+
+        let mut hm = HashSet::new();
+        let mut q = vec![r];
+
+        let mut leaf_depths = vec![];
+
+        while let Some(cur) = q.pop() {
+            let l = q.len() + 1;
+            if hm.contains(&cur) {
+                leaf_depths.push(l);
+            } else {
+                hm.insert(cur);
+                q.push(cur);
+                if let Some(x) = self.link_child[cur].0 {
+                    q.push(x);
+                }
+                if let Some(x) = self.link_child[cur].1 {
+                    q.push(x);
+                }
+            }
+        }
+
+        let total: usize = leaf_depths.iter().sum();
+        total as f32 / leaf_depths.len() as f32
+    }
+}
+
+#[test]
+fn test_treap_search() {
+    fn equal_f32(a: f32, b: f32) -> bool {
+        a - 1e-4 < b && a + 1e-4 > b
+    }
+    // test tree
+    //            6
+    //         3     8
+    //      1    5  7  10
+    //        2
+    let mut t = Treap::init();
+
+    let inst = t.new_instance();
+
+    match t.search(inst, OrdF32(5.)) {
+        SearchResult::Empty => {}
+        _ => {
+            panic!("search failure");
+        }
+    }
+
+    t.keys = vec![1., 2., 3., 5., 6., 7., 8., 10.]
+        .into_iter()
+        .map(|x| Some(OrdF32(x)))
+        .collect();
+    *t.instances.get_mut(&inst).unwrap() = Some(4);
+    t.vals = t
+        .keys
+        .iter()
+        .enumerate()
+        .map(|x| Some(x.0 as i32))
+        .collect();
+    t.link_child.resize(t.keys.len(), (None, None));
+    t.link_child[0].1 = Some(1);
+    t.link_child[2].0 = Some(0);
+    t.link_child[2].1 = Some(3);
+    t.link_child[4].0 = Some(2);
+    t.link_child[4].1 = Some(6);
+    t.link_child[6].0 = Some(5);
+    t.link_child[6].1 = Some(7);
+
+    for i in t.keys.iter().cloned().flatten() {
+        match t.search(inst, i) {
+            SearchResult::Exact(_) => {}
+            _ => {
+                panic!("search failure");
+            }
+        }
+    }
+
+    match t.search(inst, OrdF32(0.)) {
+        SearchResult::Nearest((0, k, 0)) if equal_f32(k.0, 1.) => {}
+        _ => {
+            panic!("search failure");
+        }
+    }
+
+    match t.search(inst, OrdF32(4.)) {
+        SearchResult::Nearest((3, k, 3)) if equal_f32(k.0, 5.) => {}
+        _ => {
+            panic!("search failure");
+        }
+    }
+
+    match t.search(inst, OrdF32(8.)) {
+        SearchResult::Exact((6, k, 6)) if equal_f32(k.0, 8.) => {}
+        _ => {
+            panic!("search failure");
+        }
+    }
+
+    match t.search(inst, OrdF32(99.)) {
+        SearchResult::Nearest((7, k, 7)) if equal_f32(k.0, 10.) => {}
+        _ => {
+            panic!("search failure");
+        }
+    }
+}
+
+#[test]
+fn test_treap_insert_with_priority() {
+    // test tree
+    //            6
+    //         3     8
+    //      1
+    let mut t = Treap::init();
+    let inst = t.new_instance();
+    assert_eq!(None, *t.instances.get(&inst).unwrap());
+    assert!(!t.insert_with_priority(inst, OrdF32(3.), 50., 33).0);
+    assert!(t.instances.get(&inst).unwrap().is_some());
+
+    assert!(t.insert_with_priority(inst, OrdF32(3.), 50., 3).0);
+    assert_eq!(t.insert_with_priority(inst, OrdF32(3.), 50., 3).1, 0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(1.), 75., 1).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(8.), 30., 8).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(6.), -20., 6).0);
+}
+
+#[test]
+fn test_treap_insert() {
+    let mut t = Treap::init();
+    let inst = t.new_instance();
+    assert_eq!(None, *t.instances.get(&inst).unwrap());
+    assert!(!t.insert(inst, OrdF32(3.), 33).0);
+    assert!(t.instances.get(&inst).unwrap().is_some());
+    assert!(t.insert(inst, OrdF32(3.), 3).0);
+    assert!(!t.insert(inst, OrdF32(1.), 1).0);
+    assert!(!t.insert(inst, OrdF32(8.), 8).0);
+    assert!(!t.insert(inst, OrdF32(6.), 6).0);
+}
+
+#[test]
+fn test_treap_successor() {
+    // test tree
+    //            6
+    //         3     8
+    //      1
+    let mut t = Treap::init();
+    let inst = t.new_instance();
+
+    assert!(!t.insert_with_priority(inst, OrdF32(3.), 50., 33).0);
+
+    assert!(t.insert_with_priority(inst, OrdF32(3.), 50., 3).0);
+    assert_eq!(t.insert_with_priority(inst, OrdF32(3.), 50., 3).1, 0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(1.), 75., 1).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(8.), 30., 8).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(6.), -20., 6).0);
+
+    let mut n = t.successor(1);
+    assert_eq!(0, n.unwrap());
+    n = t.successor(n.unwrap());
+    assert_eq!(3, n.unwrap());
+    n = t.successor(n.unwrap());
+    assert_eq!(2, n.unwrap());
+    assert!(t.successor(n.unwrap()).is_none());
+}
+
+#[test]
+fn test_treap_predecessor() {
+    // test tree
+    //            6
+    //         3     8
+    //      1      7
+
+    let mut t = Treap::init();
+    let inst = t.new_instance();
+
+    assert!(!t.insert_with_priority(inst, OrdF32(3.), 50., 33).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(1.), 75., 1).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(8.), 30., 8).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(6.), -20., 6).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(7.), 0., 7).0);
+
+    let mut n = t.predecessor(2);
+    assert_eq!(4, n.unwrap());
+    n = t.predecessor(n.unwrap());
+    assert_eq!(3, n.unwrap());
+    n = t.predecessor(n.unwrap());
+    assert_eq!(0, n.unwrap());
+    n = t.predecessor(n.unwrap());
+    assert_eq!(1, n.unwrap());
+    assert!(t.predecessor(n.unwrap()).is_none());
+}
+
+#[test]
+fn test_treap_query_range() {
+    fn equal_f32(a: f32, b: f32) -> bool {
+        a - 1e-4 < b && a + 1e-4 > b
+    }
+
+    // test tree
+    //            6
+    //         3     8
+    //      1      7
+
+    let mut t = Treap::init();
+    let inst = t.new_instance();
+
+    assert!(!t.insert_with_priority(inst, OrdF32(3.), 50., 33).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(1.), 75., 1).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(8.), 30., 8).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(6.), -20., 6).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(7.), 0., 7).0);
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 5);
+        let expected = [1., 3., 6., 7., 8.].to_vec();
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+    {
+        let ret = t
+            .query_range(inst, OrdF32(3.), OrdF32(8.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [3., 6., 7.].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+    {
+        let ret = t
+            .query_range(inst, OrdF32(8.5), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+    {
+        let ret = t
+            .query_range(inst, OrdF32(-10.), OrdF32(0.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+    {
+        let ret = t
+            .query_range(inst, OrdF32(-10.), OrdF32(6.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [1., 3.].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+    {
+        let ret = t
+            .query_range(inst, OrdF32(7.99), OrdF32(8.1))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [8.].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+}
+
+#[test]
+fn test_treap_remove() {
+    fn equal_f32(a: f32, b: f32) -> bool {
+        a - 1e-4 < b && a + 1e-4 > b
+    }
+
+    // test tree
+    //            6
+    //         3     8
+    //      1      7
+
+    let mut t = Treap::init();
+    let inst = t.new_instance();
+
+    assert!(!t.insert_with_priority(inst, OrdF32(3.), 50., 33).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(1.), 75., 1).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(8.), 30., 8).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(6.), -20., 6).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(7.), 0., 7).0);
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 5);
+        let expected = [1., 3., 6., 7., 8.].to_vec();
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    t.remove_index(inst, 1);
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [3., 6., 7., 8.].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    t.remove_index(inst, 0);
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [6., 7., 8.].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    t.remove_index(inst, 3);
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [7., 8.].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    t.remove_index(inst, 2);
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [7.].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+        assert_eq!(t.key(t.instances.get(&inst).unwrap().unwrap()).0, 7.);
+    }
+
+    t.remove_index(inst, 4);
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+        assert!(t.instances.get(&inst).unwrap().is_none());
+    }
+    // dbg!( &t );
+}
+
+#[test]
+fn test_treap_remove_key_range() {
+    fn equal_f32(a: f32, b: f32) -> bool {
+        a - 1e-4 < b && a + 1e-4 > b
+    }
+
+    // test tree
+    //            6
+    //         3     8
+    //      1      7
+
+    let mut t = Treap::init();
+    let inst = t.new_instance();
+
+    assert!(!t.insert_with_priority(inst, OrdF32(3.), 50., 33).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(1.), 75., 1).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(8.), 30., 8).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(6.), -20., 6).0);
+
+    assert!(!t.insert_with_priority(inst, OrdF32(7.), 0., 7).0);
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 5);
+        let expected = [1., 3., 6., 7., 8.].to_vec();
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    t.remove_key_range(inst, OrdF32(2.5), OrdF32(6.5));
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [1., 7., 8.].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    t.remove_key_range(inst, OrdF32(7.5), OrdF32(10.));
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [1., 7.].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    t.remove_key_range(inst, OrdF32(0.), OrdF32(10.));
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(10.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let expected = [].to_vec();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+        assert!(t.instances.get(&inst).unwrap().is_none());
+    }
+}
+
+#[test]
+fn test_treap_insert_remove_loop() {
+    fn equal_f32(a: f32, b: f32) -> bool {
+        a - 1e-4 < b && a + 1e-4 > b
+    }
+
+    // test tree
+    //            6
+    //         3     8
+    //      1      7
+
+    let mut t = Treap::init();
+
+    let inst = t.new_instance();
+
+    let expected = [1., 3., 6., 7., 8.];
+
+    expected.iter().for_each(|x| {
+        t.insert(inst, OrdF32(*x), *x as i32);
+    });
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    use std::collections::HashSet;
+    let mut hs = HashSet::new();
+    expected.iter().for_each(|x| {
+        hs.insert(*x as i32);
+    });
+
+    while !hs.is_empty() {
+        let remain = hs.iter().cloned().collect::<Vec<_>>();
+        let mut rng = rand::thread_rng();
+        let select = rng.gen_range(0, remain.len());
+        let key = remain[select];
+
+        let k_start = key as f32 - 1e-3;
+        let k_end = key as f32 + 1e-3;
+
+        t.remove_key_range(inst, OrdF32(k_start), OrdF32(k_end));
+
+        {
+            let ret = t
+                .query_range(inst, OrdF32(0.), OrdF32(20.))
+                .iter()
+                .map(|x| t.key(*x).0)
+                .collect::<Vec<_>>();
+            assert_eq!(ret.len(), remain.len() - 1);
+
+            ret.iter().for_each(|x| {
+                assert!(remain.iter().any(|y| equal_f32(*y as f32, *x)));
+            });
+        }
+
+        hs.remove(&key);
+        if !hs.is_empty() {
+            assert!(t.instances.get(&inst).unwrap().is_some());
+        }
+    }
+
+    assert!(t.instances.get(&inst).unwrap().is_none());
+}
+
+#[test]
+fn test_treap_split1() {
+    fn equal_f32(a: f32, b: f32) -> bool {
+        a - 1e-4 < b && a + 1e-4 > b
+    }
+
+    // test tree
+    //            6
+    //         3     8
+    //      1      7
+
+    let mut t = Treap::init();
+
+    let inst = t.new_instance();
+
+    let expected = [1., 3., 6., 7., 8.];
+
+    expected.iter().for_each(|x| {
+        t.insert(inst, OrdF32(*x), *x as i32);
+    });
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    let (inst1, inst2) = t.split(inst, OrdF32(6.5));
+
+    {
+        let ret = t
+            .query_range(inst1, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 3);
+        let check = expected.iter().take(3).zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    {
+        let ret = t
+            .query_range(inst2, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 2);
+        let check = expected.iter().skip(3).zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    let (inst3, inst4) = t.split(inst1, OrdF32(3.));
+
+    {
+        let ret = t
+            .query_range(inst3, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 1);
+        let check = expected.iter().take(1).zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    {
+        let ret = t
+            .query_range(inst4, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 2);
+        let check = expected.iter().skip(1).zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+}
+
+#[test]
+fn test_treap_split2() {
+    fn equal_f32(a: f32, b: f32) -> bool {
+        a - 1e-4 < b && a + 1e-4 > b
+    }
+
+    // test tree
+    //            6
+    //         3     8
+    //      1      7
+
+    let mut t = Treap::init();
+
+    let inst = t.new_instance();
+
+    let expected = [1., 3., 6., 7., 8.];
+
+    expected.iter().for_each(|x| {
+        t.insert(inst, OrdF32(*x), *x as i32);
+    });
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), expected.len());
+        let check = expected.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    let (inst1, inst2) = t.split(inst, OrdF32(3.));
+
+    {
+        let ret = t
+            .query_range(inst1, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 1);
+        let check = expected.iter().take(1).zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+
+    {
+        let ret = t
+            .query_range(inst2, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 4);
+        let check = expected.iter().skip(1).zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a, *b));
+        });
+    }
+}
+
+#[test]
+fn test_treap_merge() {
+    fn equal_f32(a: f32, b: f32) -> bool {
+        a - 1e-4 < b && a + 1e-4 > b
+    }
+
+    // test tree 1 vals: [1,3,4,11]
+
+    // test tree 2 vals: [12,13,17,18]
+
+    let mut t = Treap::init();
+
+    let inst1 = t.new_instance();
+    let inst2 = t.new_instance();
+
+    let expected1 = [1, 3, 4, 11];
+    let expected2 = [12, 13, 17, 18];
+
+    expected1.iter().for_each(|x| {
+        t.insert(inst1, OrdF32(*x as f32), *x);
+    });
+    expected2.iter().for_each(|x| {
+        t.insert(inst2, OrdF32(*x as f32), *x);
+    });
+
+    {
+        let ret = t
+            .query_range(inst1, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), expected1.len());
+        let check = expected1.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a as f32, *b));
+        });
+    }
+
+    {
+        let ret = t
+            .query_range(inst2, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), expected2.len());
+        let check = expected2.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a as f32, *b));
+        });
+    }
+
+    let inst3 = t.merge(inst1, inst2);
+
+    assert_eq!(inst3, inst1);
+    assert!(t.instances.get(&inst2).unwrap().is_none());
+
+    let expected_merged = expected1
+        .iter()
+        .cloned()
+        .chain(expected2.iter().cloned())
+        .collect::<Vec<_>>();
+    {
+        let ret = t
+            .query_range(inst3, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), expected_merged.len());
+        let check = expected_merged.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a as f32, *b));
+        });
+    }
+}
+
+#[test]
+fn test_treap_split_merge() {
+    fn equal_f32(a: f32, b: f32) -> bool {
+        a - 1e-4 < b && a + 1e-4 > b
+    }
+
+    // test tree vals: [1,3,4,11,12,13,17,18]
+
+    let mut t = Treap::init();
+
+    let inst1 = t.new_instance();
+
+    let expected1 = vec![1, 17, 11, 13, 18, 3, 4, 12];
+
+    expected1.iter().for_each(|x| {
+        t.insert(inst1, OrdF32(*x as f32), *x);
+    });
+
+    {
+        let ret = t
+            .query_range(inst1, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        let mut sorted = expected1.clone();
+        sorted.sort();
+        assert_eq!(ret.len(), sorted.len());
+        let check = sorted.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a as f32, *b));
+        });
+    }
+
+    let (inst2, inst3) = t.split(inst1, OrdF32(9.));
+
+    let mut split_expected1 = expected1
+        .iter()
+        .cloned()
+        .filter(|x| (*x as f32) < 9.)
+        .collect::<Vec<_>>();
+    split_expected1.sort();
+
+    let mut split_expected2 = expected1
+        .iter()
+        .cloned()
+        .filter(|x| (*x as f32) > 9.)
+        .collect::<Vec<_>>();
+    split_expected2.sort();
+
+    {
+        let ret = t
+            .query_range(inst2, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), split_expected1.len());
+        let check = split_expected1.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a as f32, *b));
+        });
+    }
+
+    {
+        let ret = t
+            .query_range(inst3, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), split_expected2.len());
+        let check = split_expected2.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a as f32, *b));
+        });
+    }
+
+    let inst4 = t.merge(inst2, inst3);
+
+    let mut expected_merged = expected1.clone();
+    expected_merged.sort();
+
+    {
+        let ret = t
+            .query_range(inst4, OrdF32(0.), OrdF32(20.))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), expected_merged.len());
+        let check = expected_merged.iter().zip(ret.iter());
+        check.for_each(|(a, b)| {
+            assert!(equal_f32(*a as f32, *b));
+        });
+    }
+}
+
+#[test]
+fn test_treap_stress() {
+    let count = 100_000;
+    let v = (0..count).map(|x| x as f32).collect::<Vec<_>>();
+    let mut t = Treap::init();
+    let inst = t.new_instance();
+
+    let t0 = Local::now();
+
+    for i in v.iter() {
+        t.insert(inst, OrdF32(*i), *i as i32);
+    }
+
+    let t1 = Local::now();
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(-1e-20), OrdF32(1e20))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), count);
+    }
+
+    let avg_depth = t.dbg_depth(inst);
+    dbg!((count as f32).log2() + 1., &avg_depth);
+
+    assert!(5. * ((count as f32).log2() + 1.) > avg_depth);
+
+    let v2 = v.iter().cloned().map(|x| (x, x + 0.5)).collect::<Vec<_>>();
+
+    let t2 = Local::now();
+
+    for i in v2.iter() {
+        t.remove_key_range(inst, OrdF32(i.0), OrdF32(i.1));
+    }
+
+    let t3 = Local::now();
+
+    {
+        let ret = t
+            .query_range(inst, OrdF32(-1e-20), OrdF32(1e20))
+            .iter()
+            .map(|x| t.key(*x).0)
+            .collect::<Vec<_>>();
+        assert_eq!(ret.len(), 0);
+    }
+
+    let t_ins = t1.signed_duration_since(t0).num_microseconds().unwrap() as f64;
+    let t_rem = t3.signed_duration_since(t2).num_microseconds().unwrap() as f64;
+
+    println!("{} us / insert", t_ins / count as f64);
+    println!("{} us / removal", t_rem / count as f64);
+}
+
+#[test]
+fn test_treap_union_intersect() {
+    let mut rng = rand::thread_rng();
+
+    let mut t = Treap::init();
+    let inst_a = t.new_instance();
+    let inst_b = t.new_instance();
+
+    let va = (0..1000)
+        .map(|_| rng.gen_range(-500, 500))
+        .collect::<HashSet<i32>>();
+    let vb = (0..1000)
+        .map(|_| rng.gen_range(-500, 500))
+        .collect::<HashSet<i32>>();
+
+    for i in va.iter() {
+        t.insert(inst_a, *i, 1);
+    }
+    for i in vb.iter() {
+        t.insert(inst_b, *i, 2);
+    }
+
+    let inst_c = t.new_instance();
+    let inst_d = t.new_instance();
+    for i in va.iter() {
+        t.insert(inst_c, *i, 1);
+    }
+    for i in vb.iter() {
+        t.insert(inst_d, *i, 2);
+    }
+
+    let u = t.union_with(inst_a, inst_b, |a, b| a + b);
+    assert_eq!(u, inst_a);
+    assert!(t.iter(inst_b).next().is_none());
+
+    let mut expected = va.union(&vb).cloned().collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(t.iter(u).map(|x| t.key(x)).collect::<Vec<_>>(), expected);
+    for x in t.iter(u) {
+        let k = t.key(x);
+        let v = match (va.contains(&k), vb.contains(&k)) {
+            (true, true) => 3,
+            (true, false) => 1,
+            _ => 2,
+        };
+        assert_eq!(t.val(x), v);
+    }
+
+    let slots_before = t.freelist.len();
+    let n = t.intersect(inst_c, inst_d);
+    let mut expected = va.intersection(&vb).cloned().collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(t.iter(n).map(|x| t.key(x)).collect::<Vec<_>>(), expected);
+    assert!(t.iter(n).all(|x| t.val(x) == 1));
+    assert_eq!(
+        t.freelist.len() - slots_before,
+        va.len() + vb.len() - expected.len()
+    );
+
+    //freed slots drop their keys and values
+    assert!(t
+        .freelist
+        .iter()
+        .all(|x| t.keys[*x].is_none() && t.vals[*x].is_none()));
+
+    //recycled slots are reused
+    let l = t.keys.len();
+    let inst_e = t.new_instance();
+    for i in 0..10 {
+        t.insert(inst_e, i, 0);
+    }
+    assert_eq!(t.keys.len(), l);
+}
+
+#[test]
+fn test_treap_range() {
+    let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
+
+    let mut t = Treap::init();
+    let inst = t.new_instance();
+
+    assert!(t.first(inst).is_none());
+    assert!(t.last(inst).is_none());
+    assert_eq!(t.iter(inst).count(), 0);
+
+    for i in items.iter() {
+        t.insert(inst, *i, format!("v{}", i));
+    }
+
+    let mut expected = items.clone();
+    expected.sort();
+
+    assert_eq!(t.key(t.first(inst).unwrap()), -45);
+    assert_eq!(t.key(t.last(inst).unwrap()), 77);
+    assert_eq!(t.val(t.first(inst).unwrap()), "v-45");
+
+    let keys = |it: Iter<i32, String>| it.map(|x| t.key(x)).collect::<Vec<_>>();
+    let filtered = |f: &dyn Fn(i32) -> bool| {
+        expected
+            .iter()
+            .cloned()
+            .filter(|x| f(*x))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(keys(t.iter(inst)), expected);
+    assert_eq!(
+        keys(t.range(inst, -9..12)),
+        filtered(&|x| (-9..12).contains(&x))
+    );
+    assert_eq!(
+        keys(t.range(inst, -9..=12)),
+        filtered(&|x| (-9..=12).contains(&x))
+    );
+    assert_eq!(keys(t.range(inst, ..7)), filtered(&|x| x < 7));
+    assert_eq!(keys(t.range(inst, 7..)), filtered(&|x| x >= 7));
+    assert_eq!(
+        t.range(inst, ..)
+            .rev()
+            .map(|x| t.key(x))
+            .collect::<Vec<_>>(),
+        expected.iter().rev().cloned().collect::<Vec<_>>()
+    );
+    assert_eq!(keys(t.range(inst, 7..=7)), vec![7]);
+    assert_eq!(keys(t.range(inst, 78..)), vec![]);
+    assert_eq!(t.query_range(inst, -9, 12).len(), 5);
+}
+
+#[test]
+fn test_treap_from_sorted() {
+    let mut t = Treap::init();
+
+    let empty = t.from_sorted(vec![]);
+    assert!(t.first(empty).is_none());
+
+    let count = 10_000;
+    let items = (0..count).map(|x| (x * 2, x)).collect::<Vec<_>>();
+
+    let inst = t.from_sorted(items.clone());
+    assert_eq!(
+        t.iter(inst)
+            .map(|x| (t.key(x), t.val(x)))
+            .collect::<Vec<_>>(),
+        items
+    );
+    for x in t.iter(inst) {
+        if let Some(y) = t.link_child[x].0 {
+            assert!(t.prio(x) <= t.prio(y));
+            assert_eq!(t.link_parent[y], x);
+        }
+        if let Some(y) = t.link_child[x].1 {
+            assert!(t.prio(x) <= t.prio(y));
+            assert_eq!(t.link_parent[y], x);
+        }
+    }
+    let avg_depth = t.dbg_depth(inst);
+    assert!(5. * ((count as f32).log2() + 1.) > avg_depth);
+
+    match t.search(inst, 4000) {
+        SearchResult::Exact((_, _, v)) => assert_eq!(v, 2000),
+        _ => panic!("search failure"),
+    }
+    t.insert(inst, 4001, -1);
+    assert_eq!(t.range(inst, 3999..4003).count(), 3);
+    t.remove_key_range(inst, 0, 4000);
+    assert_eq!(t.key(t.first(inst).unwrap()), 4000);
+
+    let inst2 = t.from_sorted_with_priority(vec![(1, 3., 1), (2, 1., 2), (3, 2., 3)]);
+    let r = t.instances[&inst2].unwrap();
+    assert_eq!(t.key(r), 2);
+    assert_eq!(t.key(t.link_child[r].0.unwrap()), 1);
+    assert_eq!(t.key(t.link_child[r].1.unwrap()), 3);
+}
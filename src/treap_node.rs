@@ -0,0 +1,386 @@
+//! node and split/join helpers shared by treap_par and treap_persistent
+//!
+//! nodes link to their children through a Ptr:
+//! Owned boxes nodes and mutates them in place,
+//! Shared counts references and copies the nodes on modified paths so previous versions stay unchanged
+
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+pub type Link<P> = Option<P>;
+
+/// pointer to a node
+pub trait Ptr<K, T>: Deref<Target = Node<K, T, Self>> + Clone + Debug + Sized
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn new(n: Node<K, T, Self>) -> Self;
+
+    /// returns the node for modification, copying it first if it is shared
+    fn make_mut(&mut self) -> &mut Node<K, T, Self>;
+}
+
+/// uniquely owned node
+pub struct Owned<K, T>(Box<Node<K, T, Owned<K, T>>>)
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug;
+
+/// reference counted node shared between versions
+pub struct Shared<K, T>(Rc<Node<K, T, Shared<K, T>>>)
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug;
+
+impl<K, T> Ptr<K, T> for Owned<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn new(n: Node<K, T, Self>) -> Self {
+        Owned(Box::new(n))
+    }
+
+    fn make_mut(&mut self) -> &mut Node<K, T, Self> {
+        &mut self.0
+    }
+}
+
+impl<K, T> Ptr<K, T> for Shared<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn new(n: Node<K, T, Self>) -> Self {
+        Shared(Rc::new(n))
+    }
+
+    fn make_mut(&mut self) -> &mut Node<K, T, Self> {
+        Rc::make_mut(&mut self.0)
+    }
+}
+
+impl<K, T> Owned<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    pub fn into_node(self) -> Node<K, T, Self> {
+        *self.0
+    }
+}
+
+impl<K, T> Shared<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<K, T> Deref for Owned<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    type Target = Node<K, T, Self>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, T> DerefMut for Owned<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<K, T> Deref for Shared<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    type Target = Node<K, T, Self>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, T> Clone for Owned<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn clone(&self) -> Self {
+        Owned(self.0.clone())
+    }
+}
+
+impl<K, T> Clone for Shared<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+impl<K, T> Debug for Owned<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<K, T> Debug for Shared<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+pub struct Node<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    pub key: K,
+    pub prio: f32,
+    pub val: T,
+    pub size: usize,
+    pub children: (Link<M>, Link<M>),
+}
+
+impl<K, T, M> Node<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    /// lower prio is closer to the root, equal priorities are ordered by key
+    pub fn ranks_above(&self, other: &Self) -> bool {
+        self.prio < other.prio || (self.prio == other.prio && self.key < other.key)
+    }
+}
+
+impl<K, T, M> Clone for Node<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            prio: self.prio,
+            val: self.val.clone(),
+            size: self.size,
+            children: self.children.clone(),
+        }
+    }
+}
+
+impl<K, T, M> Debug for Node<K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Node")
+            .field("key", &self.key)
+            .field("prio", &self.prio)
+            .field("val", &self.val)
+            .field("size", &self.size)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+pub enum SearchResult<'a, K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    Exact(&'a Node<K, T, M>),
+    Nearest(&'a Node<K, T, M>),
+    Empty,
+}
+
+///helper function
+pub fn size<K, T, M>(n: &Link<M>) -> usize
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    n.as_ref().map_or(0, |x| x.size)
+}
+
+///helper function
+pub fn update<K, T, M>(n: &mut Node<K, T, M>)
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    n.size = 1 + size(&n.children.0) + size(&n.children.1);
+}
+
+/// returns (a, b, c) such that a: [x| x.key<k], b: [x| x.key>k]
+/// and c is present if c.key == k
+pub fn split<K, T, M>(n: Link<M>, k: &K) -> (Link<M>, Link<M>, Link<M>)
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    match n {
+        None => (None, None, None),
+        Some(mut p) => {
+            let x = p.make_mut();
+            if *k < x.key {
+                let (l, r, m) = split(x.children.0.take(), k);
+                x.children.0 = r;
+                update(x);
+                (l, Some(p), m)
+            } else if x.key < *k {
+                let (l, r, m) = split(x.children.1.take(), k);
+                x.children.1 = l;
+                update(x);
+                (Some(p), r, m)
+            } else {
+                let l = x.children.0.take();
+                let r = x.children.1.take();
+                update(x);
+                (l, r, Some(p))
+            }
+        }
+    }
+}
+
+/// joins 2 trees where keys of a < keys of b
+pub fn join<K, T, M>(a: Link<M>, b: Link<M>) -> Link<M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    match (a, b) {
+        (None, x) | (x, None) => x,
+        (Some(mut a), Some(mut b)) => {
+            if a.ranks_above(&b) {
+                let x = a.make_mut();
+                x.children.1 = join(x.children.1.take(), Some(b));
+                update(x);
+                Some(a)
+            } else {
+                let y = b.make_mut();
+                y.children.0 = join(Some(a), y.children.0.take());
+                update(y);
+                Some(b)
+            }
+        }
+    }
+}
+
+pub fn search<'a, K, T, M>(n: &'a Link<M>, k: &K) -> SearchResult<'a, K, T, M>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    let mut cur = match n.as_ref() {
+        Some(x) => x,
+        _ => return SearchResult::Empty,
+    };
+    loop {
+        let next = if *k < cur.key {
+            cur.children.0.as_ref()
+        } else if cur.key < *k {
+            cur.children.1.as_ref()
+        } else {
+            return SearchResult::Exact(cur);
+        };
+        match next {
+            Some(x) => cur = x,
+            _ => return SearchResult::Nearest(cur),
+        }
+    }
+}
+
+/// return [x| x.key >= k_l && k.key < k_r ]
+pub fn query_key_range<'a, K, T, M>(n: &'a Link<M>, k_l: &K, k_r: &K) -> Vec<&'a Node<K, T, M>>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    let mut ret = vec![];
+    let mut stack = vec![];
+    let mut cur = n.as_ref();
+    loop {
+        //descend left only while the subtree can hold keys >= k_l
+        while let Some(x) = cur {
+            if x.key < *k_l {
+                cur = x.children.1.as_ref();
+            } else {
+                stack.push(x);
+                cur = x.children.0.as_ref();
+            }
+        }
+        match stack.pop() {
+            Some(x) if x.key < *k_r => {
+                ret.push(&**x);
+                cur = x.children.1.as_ref();
+            }
+            _ => break,
+        }
+    }
+    ret
+}
+
+/// returns (key, value) pairs in increasing key order
+pub fn to_vec<K, T, M>(n: &Link<M>) -> Vec<(K, T)>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+    M: Ptr<K, T>,
+{
+    let mut ret = Vec::with_capacity(size(n));
+    let mut stack = vec![];
+    let mut cur = n.as_ref();
+    loop {
+        while let Some(x) = cur {
+            stack.push(x);
+            cur = x.children.0.as_ref();
+        }
+        match stack.pop() {
+            Some(x) => {
+                ret.push((x.key.clone(), x.val.clone()));
+                cur = x.children.1.as_ref();
+            }
+            _ => break,
+        }
+    }
+    ret
+}
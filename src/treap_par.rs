@@ -0,0 +1,602 @@
+//! thread-safe treap with set operations running in parallel, based from:
+//! Fast Set Operations Using Treaps: http://www.cs.cmu.edu/afs/cs.cmu.edu/project/scandal/public/papers/treaps-spaa98.html
+//!
+//! nodes are uniquely owned so trees are Send + Sync and can be moved across threads
+//! or shared behind an Arc for concurrent reads;
+//! union and intersect fork the 2 recursive halves into scoped threads
+//! when the combined size of the operands exceeds a cutoff;
+//! each fork hands half of its thread budget to the spawned half, so at most
+//! par_threads threads (default: available parallelism) run for one operation
+
+use std::fmt::Debug;
+
+extern crate crossbeam;
+use treap::{Priority, PriorityRandom};
+use treap_node::{self, join, size, split, update, Owned, Ptr};
+
+#[cfg(test)]
+extern crate rand;
+#[cfg(test)]
+use self::rand::Rng;
+
+#[cfg(test)]
+extern crate chrono;
+#[cfg(test)]
+use self::chrono::prelude::*;
+
+/// default combined size of operands above which set operations run in parallel
+pub const PAR_CUTOFF: usize = 1 << 14;
+
+/// default number of threads a set operation may use
+pub fn default_par_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |x| x.get())
+}
+
+type Link<K, T> = treap_node::Link<Owned<K, T>>;
+
+pub type Node<K, T> = treap_node::Node<K, T, Owned<K, T>>;
+
+pub type SearchResult<'a, K, T> = treap_node::SearchResult<'a, K, T, Owned<K, T>>;
+
+#[derive(Clone, Debug)]
+pub struct TreapPar<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    root: Link<K, T>,
+    par_cutoff: usize,
+    par_threads: usize,
+}
+
+/// runs both closures, in separate threads if par is set
+fn fork<A, B, RA, RB>(par: bool, fa: A, fb: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    if par {
+        crossbeam::scope(|s| {
+            let h = s.spawn(|_| fa());
+            let rb = fb();
+            (h.join().expect("thread join"), rb)
+        })
+        .expect("thread scope")
+    } else {
+        (fa(), fb())
+    }
+}
+
+/// value of a is kept for keys present in both trees
+fn union<K, T>(a: Link<K, T>, b: Link<K, T>, cutoff: usize, threads: usize) -> Link<K, T>
+where
+    T: Clone + Debug + Send,
+    K: Ord + Clone + Debug + Send,
+{
+    match (a, b) {
+        (None, x) | (x, None) => x,
+        (Some(x), Some(y)) => {
+            let par = threads > 1 && x.size + y.size > cutoff;
+            //the spawned half gets ta threads, the current thread keeps tb
+            let (ta, tb) = if par {
+                (threads / 2, threads - threads / 2)
+            } else {
+                (threads, threads)
+            };
+
+            let a_top = x.ranks_above(&y);
+
+            let (mut top, other) = if a_top { (x, y) } else { (y, x) };
+
+            let (l2, r2, m) = split(Some(other), &top.key);
+
+            if let Some(m) = m {
+                if !a_top {
+                    top.val = m.into_node().val;
+                }
+            }
+
+            let l = top.children.0.take();
+            let r = top.children.1.take();
+
+            let (ll, rr) = if a_top {
+                fork(
+                    par,
+                    || union(l, l2, cutoff, ta),
+                    || union(r, r2, cutoff, tb),
+                )
+            } else {
+                fork(
+                    par,
+                    || union(l2, l, cutoff, ta),
+                    || union(r2, r, cutoff, tb),
+                )
+            };
+
+            top.children = (ll, rr);
+            update(&mut top);
+            Some(top)
+        }
+    }
+}
+
+/// value of a is kept
+fn intersect<K, T>(a: Link<K, T>, b: Link<K, T>, cutoff: usize, threads: usize) -> Link<K, T>
+where
+    T: Clone + Debug + Send,
+    K: Ord + Clone + Debug + Send,
+{
+    match (a, b) {
+        (None, _) | (_, None) => None,
+        (Some(x), Some(y)) => {
+            let par = threads > 1 && x.size + y.size > cutoff;
+            //the spawned half gets ta threads, the current thread keeps tb
+            let (ta, tb) = if par {
+                (threads / 2, threads - threads / 2)
+            } else {
+                (threads, threads)
+            };
+
+            let a_top = x.ranks_above(&y);
+
+            let (mut top, other) = if a_top { (x, y) } else { (y, x) };
+
+            let (l2, r2, m) = split(Some(other), &top.key);
+
+            let l = top.children.0.take();
+            let r = top.children.1.take();
+
+            let (ll, rr) = if a_top {
+                fork(
+                    par,
+                    || intersect(l, l2, cutoff, ta),
+                    || intersect(r, r2, cutoff, tb),
+                )
+            } else {
+                fork(
+                    par,
+                    || intersect(l2, l, cutoff, ta),
+                    || intersect(r2, r, cutoff, tb),
+                )
+            };
+
+            match m {
+                Some(m) => {
+                    if !a_top {
+                        top.val = m.into_node().val;
+                    }
+                    top.children = (ll, rr);
+                    update(&mut top);
+                    Some(top)
+                }
+                _ => join(ll, rr),
+            }
+        }
+    }
+}
+
+impl<K, T> Default for TreapPar<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, T> TreapPar<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            par_cutoff: PAR_CUTOFF,
+            par_threads: default_par_threads(),
+        }
+    }
+
+    /// sets the combined size of operands above which set operations run in parallel
+    pub fn set_par_cutoff(&mut self, cutoff: usize) {
+        self.par_cutoff = cutoff;
+    }
+
+    /// sets the maximum number of threads a set operation may use
+    pub fn set_par_threads(&mut self, threads: usize) {
+        self.par_threads = threads.max(1);
+    }
+
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn search(&self, k: &K) -> SearchResult<'_, K, T> {
+        treap_node::search(&self.root, k)
+    }
+
+    /// return [x| x.key >= k_l && k.key < k_r ]
+    pub fn query_key_range(&self, k_l: &K, k_r: &K) -> Vec<&Node<K, T>> {
+        treap_node::query_key_range(&self.root, k_l, k_r)
+    }
+
+    /// inserts a node and returns true if the key already exists
+    /// existing node value is updated
+    pub fn insert(&mut self, k: K, val: T) -> bool {
+        self.insert_with_priority_source(k, val, &mut PriorityRandom)
+    }
+
+    /// inserts a node with priority drawn from prio_src and returns true if the key already exists
+    /// existing node value is updated
+    pub fn insert_with_priority_source<P>(&mut self, k: K, val: T, prio_src: &mut P) -> bool
+    where
+        P: Priority<K>,
+    {
+        let (l, r, m) = split(self.root.take(), &k);
+
+        let exists = m.is_some();
+
+        let n = match m {
+            Some(mut x) => {
+                x.val = val;
+                x
+            }
+            _ => Owned::new(Node {
+                prio: prio_src.priority(&k),
+                key: k,
+                val,
+                size: 1,
+                children: (None, None),
+            }),
+        };
+
+        self.root = join(join(l, Some(n)), r);
+
+        exists
+    }
+
+    /// removes a node and returns its value
+    pub fn remove_by_key(&mut self, k: &K) -> Option<T> {
+        let (l, r, m) = split(self.root.take(), k);
+        self.root = join(l, r);
+        m.map(|x| x.into_node().val)
+    }
+
+    /// returns ((a, b), c) such that a: [x| x.key<k], b: [x| x.key>k]
+    /// and c is the (key, value) of the node with c.key == k, if present
+    pub fn split_by_key(self, k: &K) -> ((Self, Self), Option<(K, T)>) {
+        let (l, r, m) = split(self.root, k);
+        let t_l = Self {
+            root: l,
+            par_cutoff: self.par_cutoff,
+            par_threads: self.par_threads,
+        };
+        let t_r = Self {
+            root: r,
+            par_cutoff: self.par_cutoff,
+            par_threads: self.par_threads,
+        };
+        (
+            (t_l, t_r),
+            m.map(|x| {
+                let x = x.into_node();
+                (x.key, x.val)
+            }),
+        )
+    }
+
+    /// assumes a.merge(b) is such that keys of a < keys of b and returns merged tree
+    pub fn merge_contiguous(self, other: Self) -> Self {
+        Self {
+            root: join(self.root, other.root),
+            par_cutoff: self.par_cutoff,
+            par_threads: self.par_threads,
+        }
+    }
+
+    /// returns (key, value) pairs in increasing key order
+    pub fn to_vec(&self) -> Vec<(K, T)> {
+        treap_node::to_vec(&self.root)
+    }
+}
+
+impl<K, T> TreapPar<K, T>
+where
+    T: Clone + Debug + Send,
+    K: Ord + Clone + Debug + Send,
+{
+    /// returns the union of 2 trees
+    /// value of self is kept for keys present in both trees
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            root: union(self.root, other.root, self.par_cutoff, self.par_threads),
+            par_cutoff: self.par_cutoff,
+            par_threads: self.par_threads,
+        }
+    }
+
+    /// returns the intersection of 2 trees
+    /// value of self is kept
+    pub fn intersect(self, other: Self) -> Self {
+        Self {
+            root: intersect(self.root, other.root, self.par_cutoff, self.par_threads),
+            par_cutoff: self.par_cutoff,
+            par_threads: self.par_threads,
+        }
+    }
+}
+
+#[test]
+fn test_treap_par_insert() {
+    let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
+
+    let mut t = TreapPar::new();
+
+    for i in items.iter() {
+        assert!(!t.insert(*i, *i));
+    }
+
+    assert!(t.insert(6, 60));
+
+    let mut expected = items
+        .iter()
+        .map(|x| if *x == 6 { (6, 60) } else { (*x, *x) })
+        .collect::<Vec<_>>();
+    expected.sort();
+
+    assert_eq!(t.len(), items.len());
+    assert_eq!(t.to_vec(), expected);
+}
+
+#[test]
+fn test_treap_par_union_intersect() {
+    let count = 20_000;
+
+    let va = (0..count).map(|x| x * 2).collect::<Vec<i32>>();
+    let vb = (0..count).map(|x| x * 3).collect::<Vec<i32>>();
+
+    for (cutoff, threads) in [(64, 4), (64, 1), (usize::MAX, 4)].iter() {
+        let build = |items: &[i32], mult: i32| {
+            let mut t = TreapPar::new();
+            t.set_par_cutoff(*cutoff);
+            t.set_par_threads(*threads);
+            for i in items.iter() {
+                t.insert(*i, *i * mult);
+            }
+            t
+        };
+
+        {
+            let t = build(&va[..], 1).union(build(&vb[..], 10));
+
+            let mut expected = va
+                .iter()
+                .map(|x| (*x, *x))
+                .chain(
+                    vb.iter()
+                        .filter(|x| va.binary_search(x).is_err())
+                        .map(|x| (*x, *x * 10)),
+                )
+                .collect::<Vec<_>>();
+            expected.sort();
+
+            assert_eq!(t.len(), expected.len());
+            assert_eq!(t.to_vec(), expected);
+        }
+
+        {
+            let t = build(&vb[..], 10).intersect(build(&va[..], 1));
+
+            let expected = vb
+                .iter()
+                .filter(|x| *x % 2 == 0 && **x < count * 2)
+                .map(|x| (*x, *x * 10))
+                .collect::<Vec<_>>();
+
+            assert_eq!(t.len(), expected.len());
+            assert_eq!(t.to_vec(), expected);
+        }
+    }
+}
+
+#[test]
+fn test_treap_par_union_stress() {
+    let count = 200_000;
+
+    let mut rng = rand::thread_rng();
+
+    let mut t1 = TreapPar::new();
+    let mut t2 = TreapPar::new();
+
+    for _ in 0..count {
+        let a: i32 = rng.gen_range(-10_000_000, 10_000_000);
+        let b: i32 = rng.gen_range(-10_000_000, 10_000_000);
+        t1.insert(a, 0i32);
+        t2.insert(b, 0i32);
+    }
+
+    let mut expected = t1
+        .to_vec()
+        .into_iter()
+        .chain(t2.to_vec())
+        .map(|x| x.0)
+        .collect::<Vec<_>>();
+    expected.sort();
+    expected.dedup();
+
+    let ck1 = Local::now();
+
+    let t3 = t1.union(t2);
+
+    let ck2 = Local::now();
+
+    assert_eq!(
+        t3.to_vec().into_iter().map(|x| x.0).collect::<Vec<_>>(),
+        expected
+    );
+
+    let t_union = ck2.signed_duration_since(ck1).num_microseconds().unwrap() as f64;
+    println!("union of sizes({},{}): {} us", count, count, t_union);
+}
+
+#[test]
+fn test_treap_par_search_query_key_range() {
+    let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
+
+    let mut t = TreapPar::new();
+
+    match t.search(&0) {
+        SearchResult::Empty => {}
+        _ => panic!("unexpected search result"),
+    }
+
+    for i in items.iter() {
+        t.insert(*i, *i * 10);
+    }
+
+    match t.search(&7) {
+        SearchResult::Exact(x) => {
+            assert_eq!(x.key, 7);
+            assert_eq!(x.val, 70);
+        }
+        _ => panic!("unexpected search result"),
+    }
+
+    match t.search(&8) {
+        SearchResult::Nearest(x) => {
+            assert!(x.key == 7 || x.key == 9);
+        }
+        _ => panic!("unexpected search result"),
+    }
+
+    let mut expected = items.clone();
+    expected.sort();
+
+    for (k_l, k_r) in [(-100, 100), (-9, 56), (1, 1), (2, 7), (57, 77), (78, 100)].iter() {
+        let v = t
+            .query_key_range(k_l, k_r)
+            .iter()
+            .map(|x| x.key)
+            .collect::<Vec<_>>();
+        let f = expected
+            .iter()
+            .cloned()
+            .filter(|x| x >= k_l && x < k_r)
+            .collect::<Vec<_>>();
+        assert_eq!(v, f);
+    }
+
+    assert_eq!(t.remove_by_key(&6), Some(60));
+    assert_eq!(t.remove_by_key(&6), None);
+    assert_eq!(t.len(), items.len() - 1);
+}
+
+#[test]
+fn test_treap_par_split_merge() {
+    let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
+
+    let mut t = TreapPar::new();
+
+    for i in items.iter() {
+        t.insert(*i, *i);
+    }
+
+    let mut expected = items.clone();
+    expected.sort();
+
+    let ((t1, t2), m) = t.split_by_key(&6);
+
+    assert_eq!(m, Some((6, 6)));
+
+    assert_eq!(
+        t1.to_vec().iter().map(|x| x.0).collect::<Vec<_>>(),
+        expected
+            .iter()
+            .cloned()
+            .filter(|x| *x < 6)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        t2.to_vec().iter().map(|x| x.0).collect::<Vec<_>>(),
+        expected
+            .iter()
+            .cloned()
+            .filter(|x| *x > 6)
+            .collect::<Vec<_>>()
+    );
+
+    let t3 = t1.merge_contiguous(t2);
+
+    assert_eq!(t3.len(), items.len() - 1);
+    assert_eq!(
+        t3.to_vec().iter().map(|x| x.0).collect::<Vec<_>>(),
+        expected
+            .iter()
+            .cloned()
+            .filter(|x| *x != 6)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_treap_par_concurrent_read() {
+    use std::sync::Arc;
+
+    fn is_send_sync<X: Send + Sync>() {}
+    is_send_sync::<TreapPar<i32, String>>();
+
+    let count = 10_000;
+
+    let mut t = TreapPar::new();
+    for i in 0..count {
+        t.insert(i, format!("{}", i));
+    }
+
+    let t = Arc::new(t);
+
+    let handles = (0..4)
+        .map(|j| {
+            let t = t.clone();
+            std::thread::spawn(move || {
+                for i in (j..count).step_by(4) {
+                    match t.search(&i) {
+                        SearchResult::Exact(x) => assert_eq!(x.val, format!("{}", i)),
+                        _ => panic!("unexpected search result"),
+                    }
+                }
+                t.query_key_range(&(j * 100), &(j * 100 + 50)).len()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for h in handles {
+        assert_eq!(h.join().expect("thread join"), 50);
+    }
+}
+
+#[test]
+fn test_treap_par_priority_source() {
+    use treap::PrioritySeeded;
+
+    let build = || {
+        let mut src = PrioritySeeded::new(11);
+        let mut t = TreapPar::new();
+        for i in 0..100 {
+            t.insert_with_priority_source((i * 37) % 100, i, &mut src);
+        }
+        t
+    };
+
+    let t1 = build();
+    let t2 = build();
+
+    assert_eq!(t1.root.as_ref().unwrap().key, t2.root.as_ref().unwrap().key);
+    assert_eq!(t1.to_vec(), t2.to_vec());
+}
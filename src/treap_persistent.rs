@@ -0,0 +1,316 @@
+//! persistent treap with structural sharing
+//!
+//! trees are immutable; insert, remove, split and merge copy the nodes on the
+//! modified paths and return new roots, so previous versions remain valid
+//! and unchanged while sharing their untouched subtrees with newer versions
+
+use std::fmt::Debug;
+
+use treap::{Priority, PriorityRandom};
+use treap_node::{self, join, size, split, Ptr, Shared};
+
+type Link<K, T> = treap_node::Link<Shared<K, T>>;
+
+pub type Node<K, T> = treap_node::Node<K, T, Shared<K, T>>;
+
+pub type SearchResult<'a, K, T> = treap_node::SearchResult<'a, K, T, Shared<K, T>>;
+
+/// a version of the tree, cloning is O(1)
+#[derive(Clone, Debug)]
+pub struct TreapPersistent<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    root: Link<K, T>,
+}
+
+impl<K, T> Default for TreapPersistent<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, T> TreapPersistent<K, T>
+where
+    T: Clone + Debug,
+    K: Ord + Clone + Debug,
+{
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// returns true if both versions share the same root
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => a.ptr_eq(b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    pub fn search(&self, k: &K) -> SearchResult<'_, K, T> {
+        treap_node::search(&self.root, k)
+    }
+
+    /// returns a new version with the node inserted
+    /// existing node value is updated
+    pub fn insert(&self, k: K, val: T) -> Self {
+        self.insert_with_priority_source(k, val, &mut PriorityRandom)
+    }
+
+    /// returns a new version with the node inserted using a priority drawn from prio_src
+    /// existing node value is updated
+    pub fn insert_with_priority_source<P>(&self, k: K, val: T, prio_src: &mut P) -> Self
+    where
+        P: Priority<K>,
+    {
+        let (l, r, m) = split(self.root.clone(), &k);
+
+        let prio = match m {
+            Some(x) => x.prio,
+            _ => prio_src.priority(&k),
+        };
+
+        let n = Some(Shared::new(Node {
+            key: k,
+            prio,
+            val,
+            size: 1,
+            children: (None, None),
+        }));
+
+        Self {
+            root: join(join(l, n), r),
+        }
+    }
+
+    /// returns a new version with the node of key k removed
+    pub fn remove(&self, k: &K) -> Self {
+        match self.search(k) {
+            SearchResult::Exact(_) => {
+                let (l, r, _) = split(self.root.clone(), k);
+                Self { root: join(l, r) }
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// returns ((a, b), c) such that a: [x| x.key<k], b: [x| x.key>k]
+    /// and c is the (key, value) of the node with c.key == k, if present
+    pub fn split_by_key(&self, k: &K) -> ((Self, Self), Option<(K, T)>) {
+        let (l, r, m) = split(self.root.clone(), k);
+        (
+            (Self { root: l }, Self { root: r }),
+            m.map(|x| (x.key.clone(), x.val.clone())),
+        )
+    }
+
+    /// assumes a.merge(b) is such that keys of a < keys of b and returns merged tree
+    pub fn merge_contiguous(&self, other: &Self) -> Self {
+        Self {
+            root: join(self.root.clone(), other.root.clone()),
+        }
+    }
+
+    /// return [x| x.key >= k_l && k.key < k_r ]
+    pub fn query_key_range(&self, k_l: &K, k_r: &K) -> Vec<&Node<K, T>> {
+        treap_node::query_key_range(&self.root, k_l, k_r)
+    }
+
+    /// returns (key, value) pairs in increasing key order
+    pub fn to_vec(&self) -> Vec<(K, T)> {
+        treap_node::to_vec(&self.root)
+    }
+}
+
+#[test]
+fn test_treap_persistent_versions() {
+    let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
+
+    let mut versions = vec![TreapPersistent::new()];
+
+    for i in items.iter() {
+        let t = versions.last().unwrap().insert(*i, *i);
+        versions.push(t);
+    }
+
+    //update value of an existing key
+    let t = versions.last().unwrap().insert(6, 60);
+    versions.push(t);
+
+    //remove keys
+    let t = versions.last().unwrap().remove(&-45).remove(&77);
+    versions.push(t);
+
+    for (idx, v) in versions.iter().take(items.len() + 1).enumerate() {
+        let mut expected = items.iter().take(idx).map(|x| (*x, *x)).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(v.len(), idx);
+        assert_eq!(v.to_vec(), expected);
+    }
+
+    let mut expected = items
+        .iter()
+        .map(|x| if *x == 6 { (6, 60) } else { (*x, *x) })
+        .collect::<Vec<_>>();
+    expected.sort();
+
+    assert_eq!(versions[items.len() + 1].to_vec(), expected);
+
+    let expected = expected
+        .into_iter()
+        .filter(|x| x.0 != -45 && x.0 != 77)
+        .collect::<Vec<_>>();
+
+    assert_eq!(versions[items.len() + 2].to_vec(), expected);
+
+    //removing a non-existent key keeps the version
+    let t = versions.last().unwrap().remove(&1000);
+    assert!(t.ptr_eq(versions.last().unwrap()));
+
+    match versions[3].search(&1) {
+        SearchResult::Exact(x) => assert_eq!(x.val, 1),
+        _ => panic!("unexpected search result"),
+    }
+
+    match versions[2].search(&1) {
+        SearchResult::Nearest(_) => {}
+        _ => panic!("unexpected search result"),
+    }
+
+    match versions[0].search(&1) {
+        SearchResult::Empty => {}
+        _ => panic!("unexpected search result"),
+    }
+}
+
+#[test]
+fn test_treap_persistent_split_merge() {
+    let items = vec![56, -45, 1, 6, 9, -30, 7, -9, 12, 77, -25];
+
+    let mut t = TreapPersistent::new();
+
+    for i in items.iter() {
+        t = t.insert(*i, *i);
+    }
+
+    let mut expected = items.iter().map(|x| (*x, *x)).collect::<Vec<_>>();
+    expected.sort();
+
+    let ((t1, t2), m) = t.split_by_key(&6);
+
+    assert_eq!(m, Some((6, 6)));
+
+    assert_eq!(
+        t1.to_vec(),
+        expected
+            .iter()
+            .cloned()
+            .filter(|x| x.0 < 6)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        t2.to_vec(),
+        expected
+            .iter()
+            .cloned()
+            .filter(|x| x.0 > 6)
+            .collect::<Vec<_>>()
+    );
+
+    let t3 = t1.merge_contiguous(&t2);
+
+    assert_eq!(
+        t3.to_vec(),
+        expected
+            .iter()
+            .cloned()
+            .filter(|x| x.0 != 6)
+            .collect::<Vec<_>>()
+    );
+
+    //source version is unchanged
+    assert_eq!(t.to_vec(), expected);
+
+    let v = t
+        .query_key_range(&-9, &56)
+        .iter()
+        .map(|x| x.key)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        v,
+        expected
+            .iter()
+            .map(|x| x.0)
+            .filter(|x| *x >= -9 && *x < 56)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_treap_persistent_sharing() {
+    let count = 10_000;
+
+    let mut t = TreapPersistent::new();
+
+    for i in 0..count {
+        t = t.insert(i, i);
+    }
+
+    let t2 = t.insert(count, count);
+
+    //most of the nodes are shared between the 2 versions
+    fn collect<K, T>(n: &Link<K, T>, out: &mut Vec<*const Node<K, T>>)
+    where
+        T: Clone + Debug,
+        K: Ord + Clone + Debug,
+    {
+        if let Some(x) = n {
+            out.push(&**x as *const _);
+            collect(&x.children.0, out);
+            collect(&x.children.1, out);
+        }
+    }
+
+    let mut a = vec![];
+    let mut b = vec![];
+    collect(&t.root, &mut a);
+    collect(&t2.root, &mut b);
+
+    a.sort();
+    let shared = b.iter().filter(|x| a.binary_search(x).is_ok()).count();
+
+    assert_eq!(a.len(), count as usize);
+    assert_eq!(b.len(), count as usize + 1);
+    assert!(shared + 100 > count as usize);
+}
+
+#[test]
+fn test_treap_persistent_priority_source() {
+    use treap::PriorityHash;
+
+    let mut t1 = TreapPersistent::new();
+    let mut t2 = TreapPersistent::new();
+
+    for i in 0..100 {
+        t1 = t1.insert_with_priority_source(i, i, &mut PriorityHash);
+        t2 = t2.insert_with_priority_source(99 - i, 99 - i, &mut PriorityHash);
+    }
+
+    assert_eq!(t1.root.as_ref().unwrap().key, t2.root.as_ref().unwrap().key);
+    assert_eq!(t1.to_vec(), t2.to_vec());
+}